@@ -1,3 +1,20 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    DirectionChange,
+    Plateau,
+    StepTooLarge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    index: usize,
+    pair: (usize, usize),
+    violation: Violation,
+    repair: Option<usize>,
+}
+
 struct Level {
     cells: Vec<usize>,
     asc: bool,
@@ -31,16 +48,87 @@ impl Level {
         pos0.zip(pos1).all(|(a, b)| self.cmp(*a, *b))
     }
 
-    pub fn is_safe_with_tolerance(&self) -> bool {
-        if self.is_safe() {
+    // Violations are reported against the direction of the first pair, so
+    // `5 6 4 3 2` breaks at `6 -> 4` rather than at its first step.
+    pub fn diagnose(&self) -> Option<Diagnostic> {
+        let asc = self.cells.len() < 2 || self.cells[0] < self.cells[1];
+        let (index, pair) = self
+            .cells
+            .windows(2)
+            .enumerate()
+            .find(|(_, pair)| !Self::is_valid_step(asc, pair[0], pair[1]))
+            .map(|(index, pair)| (index, (pair[0], pair[1])))?;
+
+        let violation = if pair.0 == pair.1 {
+            Violation::Plateau
+        } else if (pair.0 < pair.1) != asc {
+            Violation::DirectionChange
+        } else {
+            Violation::StepTooLarge
+        };
+
+        let repair = (0..self.cells.len()).find(|index| self.is_safe_without(*index));
+
+        Some(Diagnostic {
+            index,
+            pair,
+            violation,
+            repair,
+        })
+    }
+
+    fn is_safe_without(&self, index: usize) -> bool {
+        let rest = || {
+            self.cells
+                .iter()
+                .enumerate()
+                .filter(move |(i, _)| *i != index)
+                .map(|(_, cell)| *cell)
+        };
+        let asc = rest()
+            .next()
+            .zip(rest().next_back())
+            .map(|(f, l)| f < l)
+            .unwrap_or(true);
+
+        rest()
+            .zip(rest().skip(1))
+            .all(|(a, b)| Self::is_valid_step(asc, a, b))
+    }
+
+    // removals[i] is the minimum amount of cells to remove so that cells[i] is kept and
+    // everything up to it is safe. Only the previous `tolerance + 1` cells can come right before it.
+    pub fn is_safe_with_tolerance(&self, tolerance: usize) -> bool {
+        if self.cells.len() <= tolerance + 1 {
             return true;
         }
 
-        (0..self.cells.len())
-            .into_iter()
-            .any(|index| self.without(index).is_safe())
+        let mut removals = vec![0; self.cells.len()];
+        [true, false]
+            .iter()
+            .any(|asc| self.min_removals(*asc, tolerance, &mut removals) <= tolerance)
+    }
+
+    fn min_removals(&self, asc: bool, tolerance: usize, removals: &mut [usize]) -> usize {
+        let len = self.cells.len();
+        let mut best = len;
+
+        for i in 0..len {
+            removals[i] = i;
+            for j in i.saturating_sub(tolerance + 1)..i {
+                let candidate = removals[j] + (i - j - 1);
+                if candidate < removals[i] && Self::is_valid_step(asc, self.cells[j], self.cells[i])
+                {
+                    removals[i] = candidate;
+                }
+            }
+            best = best.min(removals[i] + (len - i - 1));
+        }
+
+        best
     }
 
+    #[cfg(test)]
     pub fn without(&self, index: usize) -> Level {
         let mut vec = Vec::with_capacity(self.cells.len() - 1);
         vec.extend_from_slice(&self.cells[0..index]);
@@ -49,7 +137,11 @@ impl Level {
     }
 
     fn cmp(&self, n1: usize, n2: usize) -> bool {
-        if self.asc {
+        Self::is_valid_step(self.asc, n1, n2)
+    }
+
+    fn is_valid_step(asc: bool, n1: usize, n2: usize) -> bool {
+        if asc {
             n1 < n2 && (n2 - n1) <= 3
         } else {
             n2 < n1 && (n1 - n2) <= 3
@@ -72,11 +164,54 @@ fn day2_step2(input: &str) -> usize {
         .filter(|s| !s.is_empty())
         .map(Level::from_str)
         .collect();
-    levels.iter().filter(|l| l.is_safe_with_tolerance()).count()
+    levels
+        .iter()
+        .filter(|l| l.is_safe_with_tolerance(1))
+        .count()
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::DirectionChange => write!(f, "direction change"),
+            Violation::Plateau => write!(f, "plateau"),
+            Violation::StepTooLarge => write!(f, "step too large"),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unsafe: {} -> {} at {} ({})",
+            self.pair.0, self.pair.1, self.index, self.violation
+        )?;
+        match self.repair {
+            Some(index) => write!(f, ", fixed by removing index {}", index),
+            None => write!(f, ", no single removal fixes it"),
+        }
+    }
+}
+
+fn day2_report(input: &str) -> String {
+    input
+        .lines()
+        .filter(|s| !s.is_empty())
+        .map(|line| match Level::from_str(line).diagnose() {
+            Some(diagnostic) => format!("{}: {}\n", line, diagnostic),
+            None => format!("{}: safe\n", line),
+        })
+        .collect()
 }
 
 fn main() {
     let input = include_str!("../input.txt");
+    if std::env::args().any(|arg| arg == "--report") {
+        print!("{}", day2_report(input));
+        return;
+    }
+
     let step1 = day2_step1(input);
     let step2 = day2_step2(input);
 
@@ -156,4 +291,167 @@ mod tests {
         );
         assert_eq!(day2_step1(input), 2);
     }
+
+    #[test]
+    fn safe_level_has_no_diagnostic() {
+        assert_eq!(Level::from_str("7 6 4 2 1").diagnose(), None);
+    }
+
+    #[test]
+    fn diagnose_step_too_large_without_repair() {
+        let diagnostic = Level::from_str("1 2 7 8 9").diagnose().unwrap();
+
+        assert_eq!(
+            diagnostic,
+            Diagnostic {
+                index: 1,
+                pair: (2, 7),
+                violation: Violation::StepTooLarge,
+                repair: None,
+            }
+        );
+    }
+
+    #[test]
+    fn diagnose_direction_change_with_repair() {
+        let diagnostic = Level::from_str("1 3 2 4 5").diagnose().unwrap();
+
+        assert_eq!(diagnostic.index, 1);
+        assert_eq!(diagnostic.pair, (3, 2));
+        assert_eq!(diagnostic.violation, Violation::DirectionChange);
+        assert_eq!(diagnostic.repair, Some(1));
+    }
+
+    #[test]
+    fn diagnose_direction_change_against_the_first_pair() {
+        let diagnostic = Level::from_str("5 6 4 3 2").diagnose().unwrap();
+
+        assert_eq!(diagnostic.index, 1);
+        assert_eq!(diagnostic.pair, (6, 4));
+        assert_eq!(diagnostic.violation, Violation::DirectionChange);
+        assert_eq!(diagnostic.repair, Some(0));
+    }
+
+    #[test]
+    fn diagnose_plateau_with_repair() {
+        let diagnostic = Level::from_str("8 6 4 4 1").diagnose().unwrap();
+
+        assert_eq!(diagnostic.pair, (4, 4));
+        assert_eq!(diagnostic.violation, Violation::Plateau);
+        assert_eq!(diagnostic.repair, Some(2));
+    }
+
+    #[test]
+    fn report_annotates_every_line() {
+        let input = concat!("7 6 4 2 1\n", "1 2 7 8 9\n", "1 3 2 4 5\n");
+
+        assert_eq!(
+            day2_report(input),
+            concat!(
+                "7 6 4 2 1: safe\n",
+                "1 2 7 8 9: unsafe: 2 -> 7 at 1 (step too large), no single removal fixes it\n",
+                "1 3 2 4 5: unsafe: 3 -> 2 at 1 (direction change), fixed by removing index 1\n",
+            )
+        );
+    }
+
+    #[test]
+    fn repair_agrees_with_tolerance_1_on_generated_levels() {
+        for level in generate_levels(0x0000_2702, 2000) {
+            let repaired = level.diagnose().map(|d| d.repair.is_some()).unwrap_or(true);
+            assert_eq!(
+                repaired,
+                level.is_safe_with_tolerance(1),
+                "{:?}",
+                level.cells
+            );
+        }
+    }
+
+    fn brute_force_tolerance(level: &Level, tolerance: usize) -> bool {
+        if level.is_safe() {
+            return true;
+        }
+        if tolerance == 0 {
+            return false;
+        }
+
+        (0..level.cells.len())
+            .any(|index| brute_force_tolerance(&level.without(index), tolerance - 1))
+    }
+
+    fn generate_levels(seed: u64, amount: usize) -> Vec<Level> {
+        let mut state = seed;
+        let mut next = move |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+
+        (0..amount)
+            .map(|_| {
+                let len = 1 + next(9) as usize;
+                let mut current = 1 + next(20) as usize;
+                let cells = (0..len)
+                    .map(|_| {
+                        let delta = next(6) as usize;
+                        current = if next(2) == 0 {
+                            current + delta
+                        } else {
+                            current.saturating_sub(delta)
+                        };
+                        current
+                    })
+                    .collect();
+                Level::new(cells)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tolerance_0_is_the_same_as_safe() {
+        let level = Level::from_str("1 3 2 4 5");
+
+        assert!(!level.is_safe_with_tolerance(0));
+        assert!(Level::from_str("1 3 4 5").is_safe_with_tolerance(0));
+    }
+
+    #[test]
+    fn tolerance_2_allows_two_removals() {
+        let level = Level::from_str("1 9 2 9 3 4");
+
+        assert!(!level.is_safe_with_tolerance(1));
+        assert!(level.is_safe_with_tolerance(2));
+    }
+
+    #[test]
+    fn removing_the_first_cell_can_change_direction() {
+        let level = Level::from_str("9 1 2 3");
+
+        assert!(level.is_safe_with_tolerance(1));
+    }
+
+    #[test]
+    fn tolerance_bigger_than_the_level_is_safe() {
+        let level = Level::from_str("1 1 1");
+
+        assert!(level.is_safe_with_tolerance(2));
+        assert!(!level.is_safe_with_tolerance(1));
+    }
+
+    #[test]
+    fn tolerance_matches_brute_force_on_generated_levels() {
+        for level in generate_levels(0x2024_1202, 2000) {
+            for tolerance in 0..4 {
+                assert_eq!(
+                    level.is_safe_with_tolerance(tolerance),
+                    brute_force_tolerance(&level, tolerance),
+                    "{:?} with tolerance {}",
+                    level.cells,
+                    tolerance
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,326 @@
+use crate::lexer::{Lexer, Token, TokenKind};
+
+pub const GRAMMAR: &str = concat!(
+    "program     = { garbage | instruction } ;\n",
+    "instruction = call | \"do()\" | \"don't()\" ;\n",
+    "call        = operator \"(\" operand \",\" operand \")\" ;\n",
+    "operator    = \"mul\" | \"add\" | \"sub\" | \"div\" ;    (* only \"mul\" in the classic set *)\n",
+    "operand     = number | call ;                   (* only number in the classic set, at most 32 calls deep *)\n",
+    "number      = digit { digit } ;                 (* 1 to 3 digits with strict operands *)\n",
+    "garbage     = ? any byte ? ;\n",
+);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstructionSet {
+    Classic,
+    Extended,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OperandLimit {
+    Strict,
+    Unlimited,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Syntax {
+    pub instruction_set: InstructionSet,
+    pub operand_limit: OperandLimit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Mul,
+    Add,
+    Sub,
+    Div,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(usize),
+    Call(Operator, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EvalError {
+    Overflow,
+    Underflow,
+    DivisionByZero,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    Eval(Expr),
+    Do,
+    Dont,
+}
+
+impl Syntax {
+    pub fn classic() -> Syntax {
+        Syntax {
+            instruction_set: InstructionSet::Classic,
+            operand_limit: OperandLimit::Strict,
+        }
+    }
+
+    fn operators(&self) -> &'static [Operator] {
+        match self.instruction_set {
+            InstructionSet::Classic => &[Operator::Mul],
+            InstructionSet::Extended => &[Operator::Mul, Operator::Add, Operator::Sub, Operator::Div],
+        }
+    }
+}
+
+impl Operator {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operator::Mul => "mul",
+            Operator::Add => "add",
+            Operator::Sub => "sub",
+            Operator::Div => "div",
+        }
+    }
+
+    fn apply(&self, n1: usize, n2: usize) -> Result<usize, EvalError> {
+        match self {
+            Operator::Mul => n1.checked_mul(n2).ok_or(EvalError::Overflow),
+            Operator::Add => n1.checked_add(n2).ok_or(EvalError::Overflow),
+            Operator::Sub => n1.checked_sub(n2).ok_or(EvalError::Underflow),
+            Operator::Div => n1.checked_div(n2).ok_or(EvalError::DivisionByZero),
+        }
+    }
+}
+
+impl Expr {
+    pub fn evaluate(&self) -> Result<usize, EvalError> {
+        match self {
+            Expr::Number(n) => Ok(*n),
+            Expr::Call(operator, e1, e2) => operator.apply(e1.evaluate()?, e2.evaluate()?),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedInstruction {
+    pub instruction: Instruction,
+    pub start: usize,
+    pub end: usize,
+}
+
+pub struct PartialParse {
+    pub instructions: Vec<ParsedInstruction>,
+    pub consumed: usize,
+}
+
+const MAX_OPERAND_DIGITS: usize = 3;
+
+// Calls nested deeper than this are not instructions. It keeps the recursion
+// of the parser (and of `Expr::evaluate`) bounded on corrupted input, and so
+// the part of it that a `Scanner` carries over to the next chunk.
+const MAX_NESTING: usize = 32;
+
+// Names are matched as a suffix of a word, so only this many of its last bytes
+// can make a difference.
+const LONGEST_NAME: usize = "don't".len();
+
+enum Miss {
+    NoMatch,
+    Incomplete,
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    tokens: Vec<Token>,
+    pending: Option<Token>,
+    syntax: Syntax,
+}
+
+impl<'a> Parser<'a> {
+    // When more input can follow, the last token could still grow (`mu` + `l(1,2)`),
+    // so it is left out as `pending`. Only the part of the input that can still
+    // become an instruction is reported as not consumed, so a long run of
+    // letters or digits is not carried over (and lexed again) chunk after chunk.
+    fn new(input: &'a [u8], at_eof: bool, syntax: Syntax) -> Parser<'a> {
+        let mut tokens: Vec<Token> = Lexer::new(input).collect();
+        let pending = if at_eof { None } else { tokens.pop() };
+
+        Parser {
+            input,
+            tokens,
+            pending,
+            syntax,
+        }
+    }
+
+    fn parse(&self) -> PartialParse {
+        let mut instructions = vec![];
+        let mut index = 0;
+
+        while index < self.tokens.len() {
+            match self.instruction_at(index) {
+                Ok((parsed, next)) => {
+                    instructions.push(parsed);
+                    index = next;
+                }
+                Err(Miss::NoMatch) => index += 1,
+                Err(Miss::Incomplete) => {
+                    return PartialParse {
+                        instructions,
+                        consumed: self.tokens[index].start,
+                    }
+                }
+            }
+        }
+
+        let consumed = match &self.pending {
+            None => self.input.len(),
+            Some(token) if token.kind == TokenKind::Word => {
+                token.start.max(token.end.saturating_sub(LONGEST_NAME))
+            }
+            Some(token) => token.end,
+        };
+        PartialParse {
+            instructions,
+            consumed,
+        }
+    }
+
+    // Words can have garbage glued before the name (`xmul(1,2)`), so the
+    // instruction name is matched as a suffix of the word.
+    fn instruction_at(&self, index: usize) -> Result<(ParsedInstruction, usize), Miss> {
+        let word = self
+            .tokens
+            .get(index)
+            .filter(|t| t.kind == TokenKind::Word)
+            .ok_or(Miss::NoMatch)?;
+        let text = word.text(self.input);
+
+        let mut cursor = index + 1;
+        let (instruction, name_len) = if text.ends_with(b"don't") {
+            self.no_arguments(&mut cursor)?;
+            (Instruction::Dont, "don't".len())
+        } else if text.ends_with(b"do") {
+            self.no_arguments(&mut cursor)?;
+            (Instruction::Do, "do".len())
+        } else {
+            let operator = self
+                .syntax
+                .operators()
+                .iter()
+                .find(|o| text.ends_with(o.name().as_bytes()))
+                .ok_or(Miss::NoMatch)?;
+            let call = self.call_arguments(*operator, &mut cursor, 0)?;
+            (Instruction::Eval(call), operator.name().len())
+        };
+
+        let parsed = ParsedInstruction {
+            instruction,
+            start: word.end - name_len,
+            end: self.tokens[cursor - 1].end,
+        };
+        Ok((parsed, cursor))
+    }
+
+    fn no_arguments(&self, cursor: &mut usize) -> Result<(), Miss> {
+        self.expect(cursor, TokenKind::OpenParen)?;
+        self.expect(cursor, TokenKind::CloseParen)?;
+        Ok(())
+    }
+
+    fn call_arguments(&self, operator: Operator, cursor: &mut usize, depth: usize) -> Result<Expr, Miss> {
+        self.expect(cursor, TokenKind::OpenParen)?;
+        let e1 = self.operand(cursor, depth)?;
+        self.expect(cursor, TokenKind::Comma)?;
+        let e2 = self.operand(cursor, depth)?;
+        self.expect(cursor, TokenKind::CloseParen)?;
+        Ok(Expr::Call(operator, Box::new(e1), Box::new(e2)))
+    }
+
+    fn expect(&self, cursor: &mut usize, kind: TokenKind) -> Result<&Token, Miss> {
+        let token = self
+            .tokens
+            .get(*cursor)
+            .ok_or_else(|| self.missing(|pending| pending.kind == kind))?;
+        if token.kind != kind {
+            return Err(Miss::NoMatch);
+        }
+        *cursor += 1;
+        Ok(token)
+    }
+
+    fn operand(&self, cursor: &mut usize, depth: usize) -> Result<Expr, Miss> {
+        let token = self
+            .tokens
+            .get(*cursor)
+            .ok_or_else(|| self.missing(|pending| self.could_be_operand(pending)))?;
+        match token.kind {
+            TokenKind::Number => self.number(cursor).map(Expr::Number),
+            TokenKind::Word if self.syntax.instruction_set == InstructionSet::Extended => {
+                let text = token.text(self.input);
+                let operator = self
+                    .syntax
+                    .operators()
+                    .iter()
+                    .find(|o| text == o.name().as_bytes())
+                    .ok_or(Miss::NoMatch)?;
+                *cursor += 1;
+                if depth + 1 == MAX_NESTING {
+                    return Err(Miss::NoMatch);
+                }
+                self.call_arguments(*operator, cursor, depth + 1)
+            }
+            _ => Err(Miss::NoMatch),
+        }
+    }
+
+    fn number(&self, cursor: &mut usize) -> Result<usize, Miss> {
+        let token = self.expect(cursor, TokenKind::Number)?;
+        self.value(token).ok_or(Miss::NoMatch)
+    }
+
+    fn value(&self, token: &Token) -> Option<usize> {
+        if self.syntax.operand_limit == OperandLimit::Strict && token.len() > MAX_OPERAND_DIGITS {
+            return None;
+        }
+        token
+            .text(self.input)
+            .iter()
+            .try_fold(0usize, |acc, digit| {
+                acc.checked_mul(10)?.checked_add(usize::from(digit - b'0'))
+            })
+    }
+
+    // The tokens ran out. Whether more input can complete the instruction
+    // depends on the pending token, which can only get longer.
+    fn missing(&self, could_grow_into: impl Fn(&Token) -> bool) -> Miss {
+        match &self.pending {
+            Some(pending) if could_grow_into(pending) => Miss::Incomplete,
+            _ => Miss::NoMatch,
+        }
+    }
+
+    // More digits never make a number valid again, and more letters can only
+    // turn a word into an operator name if it is the start of one.
+    fn could_be_operand(&self, token: &Token) -> bool {
+        match token.kind {
+            TokenKind::Number => self.value(token).is_some(),
+            TokenKind::Word if self.syntax.instruction_set == InstructionSet::Extended => {
+                let text = token.text(self.input);
+                self.syntax
+                    .operators()
+                    .iter()
+                    .any(|o| o.name().as_bytes().starts_with(text))
+            }
+            _ => false,
+        }
+    }
+}
+
+pub fn parse(input: &[u8], syntax: Syntax) -> Vec<ParsedInstruction> {
+    parse_partial(input, true, syntax).instructions
+}
+
+pub fn parse_partial(input: &[u8], at_eof: bool, syntax: Syntax) -> PartialParse {
+    Parser::new(input, at_eof, syntax).parse()
+}
@@ -0,0 +1,97 @@
+use crate::instruction::{parse, EvalError, Instruction, Syntax};
+use crate::scanner::Scanner;
+use crate::trace::TraceEntry;
+use std::io::{self, Read};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Unconditional,
+    Conditional,
+}
+
+pub struct Interpreter {
+    mode: Mode,
+    syntax: Syntax,
+    enabled: bool,
+    sum: usize,
+}
+
+impl Interpreter {
+    pub fn new(mode: Mode) -> Interpreter {
+        Self::with_syntax(mode, Syntax::classic())
+    }
+
+    pub fn with_syntax(mode: Mode, syntax: Syntax) -> Interpreter {
+        Interpreter {
+            mode,
+            syntax,
+            enabled: true,
+            sum: 0,
+        }
+    }
+
+    pub fn run(&mut self, input: &str) -> usize {
+        self.trace(input);
+        self.sum
+    }
+
+    #[cfg(test)]
+    pub fn run_stream<R: Read>(&mut self, scanner: Scanner<R>) -> io::Result<usize> {
+        Self::run_stream_all(&mut [&mut *self], scanner)?;
+        Ok(self.sum)
+    }
+
+    // Reads the stream once and runs every instruction on all the interpreters.
+    pub fn run_stream_all<R: Read>(
+        interpreters: &mut [&mut Interpreter],
+        scanner: Scanner<R>,
+    ) -> io::Result<()> {
+        for parsed in scanner {
+            let parsed = parsed?;
+            for interpreter in interpreters.iter_mut() {
+                // Failed evaluations are skipped; use `trace` to see them.
+                let _ = interpreter.execute(&parsed.instruction);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn trace(&mut self, input: &str) -> Vec<TraceEntry> {
+        parse(input.as_bytes(), self.syntax)
+            .into_iter()
+            .map(|parsed| {
+                let error = self.execute(&parsed.instruction).err();
+                TraceEntry {
+                    parsed,
+                    enabled: self.enabled,
+                    error,
+                    sum: self.sum,
+                }
+            })
+            .collect()
+    }
+
+    pub fn sum(&self) -> usize {
+        self.sum
+    }
+
+    pub fn execute(&mut self, instruction: &Instruction) -> Result<(), EvalError> {
+        match instruction {
+            Instruction::Eval(expr) => {
+                if self.enabled {
+                    let value = expr.evaluate()?;
+                    self.sum = self.sum.checked_add(value).ok_or(EvalError::Overflow)?;
+                }
+            }
+            Instruction::Do => self.set_enabled(true),
+            Instruction::Dont => self.set_enabled(false),
+        }
+        Ok(())
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        if self.mode == Mode::Conditional {
+            self.enabled = enabled;
+        }
+    }
+}
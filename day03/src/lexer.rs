@@ -0,0 +1,81 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    Word,
+    Number,
+    OpenParen,
+    CloseParen,
+    Comma,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+pub struct Lexer<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a [u8]) -> Lexer<'a> {
+        Lexer {
+            input,
+            position: 0,
+        }
+    }
+
+    fn take_while(&mut self, predicate: fn(u8) -> bool) {
+        while self.position < self.input.len() && predicate(self.input[self.position]) {
+            self.position += 1;
+        }
+    }
+}
+
+fn is_word_byte(byte: u8) -> bool {
+    byte.is_ascii_alphabetic() || byte == b'\''
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let start = self.position;
+        let byte = *self.input.get(start)?;
+        self.position += 1;
+
+        let kind = match byte {
+            b'(' => TokenKind::OpenParen,
+            b')' => TokenKind::CloseParen,
+            b',' => TokenKind::Comma,
+            b if b.is_ascii_digit() => {
+                self.take_while(|b| b.is_ascii_digit());
+                TokenKind::Number
+            }
+            b if is_word_byte(b) => {
+                self.take_while(is_word_byte);
+                TokenKind::Word
+            }
+            _ => TokenKind::Other,
+        };
+
+        Some(Token {
+            kind,
+            start,
+            end: self.position,
+        })
+    }
+}
+
+impl Token {
+    pub fn text<'a>(&self, input: &'a [u8]) -> &'a [u8] {
+        &input[self.start..self.end]
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+}
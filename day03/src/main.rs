@@ -1,51 +1,86 @@
-use regex::Regex;
+use crate::instruction::{InstructionSet, OperandLimit, Syntax, GRAMMAR};
+use crate::interpreter::{Interpreter, Mode};
+use crate::scanner::Scanner;
+use crate::trace::highlight;
+use std::fs::File;
+
+mod instruction;
+mod interpreter;
+mod lexer;
+mod scanner;
+mod trace;
 
 fn step1(input: &str) -> usize {
-    let reg = Regex::new(r"mul\((\d\d?\d?),(\d\d?\d?)\)").unwrap();
-    let instances = reg.captures_iter(input).map(|c| c.extract::<2>());
-
-    let sum = instances
-        .map(|(_, n)| n)
-        .map(|strs| (strs[0].parse::<usize>().unwrap(), strs[1].parse::<usize>().unwrap()))
-        .map(|(n1, n2)| n1 * n2)
-        .sum();
-    sum
+    Interpreter::new(Mode::Unconditional).run(input)
 }
 
 fn step2(input: &str) -> usize {
-    let reg = Regex::new(r"(mul\(\d{1,3},\d{1,3}\))|(do\(\))|(don't\(\))").unwrap();
-    let instances = reg.captures_iter(input).map(|c| c.extract::<1>());
-
-    let mut res: usize = 0;
-    let mut enabled = true;
-
-    for (_, capture) in instances {
-        if capture[0].starts_with("mul(") && enabled {
-            let coso = capture[0]
-                .to_string()
-                .replace("mul(", "")
-                .replace(")", "");
-            let mut parts = coso
-                .split(",");
-            let n1 = parts.next().unwrap().parse::<usize>().unwrap();
-            let n2 = parts.next().unwrap().parse::<usize>().unwrap();
-            res = res + n1 * n2;
+    Interpreter::new(Mode::Conditional).run(input)
+}
+
+fn main() {
+    let input = include_str!("../input.txt");
+    let args: Vec<String> = std::env::args().collect();
+    let has_flag = |flag: &str| args.iter().any(|arg| arg == flag);
+    let syntax = Syntax {
+        instruction_set: if has_flag("--extended") {
+            InstructionSet::Extended
+        } else {
+            InstructionSet::Classic
+        },
+        operand_limit: if has_flag("--unlimited") {
+            OperandLimit::Unlimited
+        } else {
+            OperandLimit::Strict
+        },
+    };
+
+    if has_flag("--grammar") {
+        print!("{}", GRAMMAR);
+        return;
+    }
+
+    if let Some(path) = args
+        .iter()
+        .position(|arg| arg == "--file")
+        .and_then(|index| args.get(index + 1))
+    {
+        let file = File::open(path).unwrap_or_else(|e| {
+            eprintln!("cannot open {}: {}", path, e);
+            std::process::exit(1)
+        });
+
+        let mut step1 = Interpreter::with_syntax(Mode::Unconditional, syntax);
+        let mut step2 = Interpreter::with_syntax(Mode::Conditional, syntax);
+        let scanner = Scanner::new(file, syntax);
+        if let Err(e) = Interpreter::run_stream_all(&mut [&mut step1, &mut step2], scanner) {
+            eprintln!("cannot read {}: {}", path, e);
+            std::process::exit(1);
         }
-        if capture[0].starts_with("do(") {
-            enabled = true;
+        println!("step1: {}", step1.sum());
+        println!("step2: {}", step2.sum());
+        return;
+    }
+
+    if has_flag("--trace") || has_flag("--highlight") {
+        let trace = Interpreter::with_syntax(Mode::Conditional, syntax).trace(input);
+        if has_flag("--trace") {
+            trace.iter().for_each(|entry| println!("{}", entry));
         }
-        if capture[0].starts_with("don't(") {
-            enabled = false;
+        if has_flag("--highlight") {
+            println!("{}", highlight(input, &trace));
         }
+        return;
     }
 
-    res
-}
-
-fn main() {
-    let input = include_str!("../input.txt");
-    let res1 = step1(input);
-    let res2 = step2(input);
+    let (res1, res2) = if syntax == Syntax::classic() {
+        (step1(input), step2(input))
+    } else {
+        (
+            Interpreter::with_syntax(Mode::Unconditional, syntax).run(input),
+            Interpreter::with_syntax(Mode::Conditional, syntax).run(input),
+        )
+    };
 
     println!("step1: {}", res1);
     println!("step2: {}", res2);
@@ -55,6 +90,7 @@ fn main() {
 #[cfg(test)]
 mod tests{
     use super::*;
+    use crate::instruction::{parse_partial, EvalError};
     #[test]
     fn test_01() {
         let input = "mul(1,2) mul(3,4)";
@@ -76,4 +112,224 @@ mod tests{
         let input = "mul(1,2) mul(3,4) don't() mul(1,1) do() mul(3, 1)";
         assert_eq!(step2(input), 17);
     }
+
+    #[test]
+    fn test_04() {
+        let input = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+        assert_eq!(step1(input), 161);
+        assert_eq!(step2(input), 48);
+    }
+
+    #[test]
+    fn test_05() {
+        let input = "mul(1234,2) mul(2,3) mul( 2,3) mul(4,5";
+        assert_eq!(step1(input), 6);
+    }
+
+    #[test]
+    fn trace_lists_every_instruction_with_running_sum() {
+        let input = "xmul(2,4)don't()mul(5,5)do()mul(1,3)";
+        let trace = Interpreter::new(Mode::Conditional).trace(input);
+
+        let summary: Vec<(usize, String, bool, usize)> = trace
+            .iter()
+            .map(|e| (e.parsed.start, e.parsed.instruction.to_string(), e.enabled, e.sum))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, "mul(2,4)".to_string(), true, 8),
+                (9, "don't()".to_string(), false, 8),
+                (16, "mul(5,5)".to_string(), false, 8),
+                (24, "do()".to_string(), true, 8),
+                (28, "mul(1,3)".to_string(), true, 11),
+            ]
+        );
+    }
+
+    #[test]
+    fn trace_entry_display() {
+        let trace = Interpreter::new(Mode::Conditional).trace("..mul(2,4)");
+        assert_eq!(trace[0].to_string(), "     2  mul(2,4)     enabled  sum=8");
+    }
+
+    #[test]
+    fn highlight_wraps_instructions_in_colors() {
+        let input = "xmul(2,4)don't()mul(5,5)";
+        let trace = Interpreter::new(Mode::Conditional).trace(input);
+
+        assert_eq!(
+            highlight(input, &trace),
+            "x\x1b[32mmul(2,4)\x1b[0m\x1b[33mdon't()\x1b[0m\x1b[31mmul(5,5)\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn stream_gives_same_result_for_any_chunk_size() {
+        let input = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))mul(123,456)";
+        let expected = step2(input);
+
+        for chunk_size in 1..=16 {
+            let scanner = Scanner::with_chunk_size(input.as_bytes(), Syntax::classic(), chunk_size);
+            let res = Interpreter::new(Mode::Conditional).run_stream(scanner).unwrap();
+            assert_eq!(res, expected, "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn one_stream_feeds_both_steps() {
+        let input = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+        let mut unconditional = Interpreter::new(Mode::Unconditional);
+        let mut conditional = Interpreter::new(Mode::Conditional);
+        let scanner = Scanner::with_chunk_size(input.as_bytes(), Syntax::classic(), 5);
+        Interpreter::run_stream_all(&mut [&mut unconditional, &mut conditional], scanner).unwrap();
+
+        assert_eq!(unconditional.sum(), step1(input));
+        assert_eq!(conditional.sum(), step2(input));
+    }
+
+    #[test]
+    fn stream_reports_offsets_from_the_start() {
+        let input = "..mul(1,2)...do()..don't()";
+        let offsets: Vec<usize> = Scanner::with_chunk_size(input.as_bytes(), Syntax::classic(), 3)
+            .map(|parsed| parsed.unwrap().start)
+            .collect();
+
+        assert_eq!(offsets, vec![2, 13, 19]);
+    }
+
+    #[test]
+    fn stream_tolerates_invalid_utf8() {
+        let input: &[u8] = b"\xffmul(2,3)\xfe\xc3mul(4,5)\xc3(mul(1,\x80)";
+        let scanner = Scanner::with_chunk_size(input, Syntax::classic(), 4);
+
+        assert_eq!(Interpreter::new(Mode::Unconditional).run_stream(scanner).unwrap(), 26);
+    }
+
+    #[test]
+    fn stream_only_carries_over_what_can_become_an_instruction() {
+        let consumed = |input: &str| parse_partial(input.as_bytes(), false, Syntax::classic()).consumed;
+
+        assert_eq!(consumed("mul(1,2)xxxxxxxxdon"), 14);
+        assert_eq!(consumed("mul(1,2)1234567890"), 18);
+        assert_eq!(consumed("..mul(12"), 2);
+        assert_eq!(consumed("..mul(1234"), 10);
+        assert_eq!(consumed("..mul(1,"), 2);
+        assert_eq!(consumed("..mul(1x"), 7);
+    }
+
+    #[test]
+    fn stream_handles_long_runs_of_letters_and_digits() {
+        let input = format!(
+            "{}mul(2,3){}don't(){}do()mul(4,5)",
+            "x".repeat(1000),
+            "7".repeat(1000),
+            "a".repeat(1000)
+        );
+
+        for chunk_size in [1, 7, 64] {
+            let scanner = Scanner::with_chunk_size(input.as_bytes(), Syntax::classic(), chunk_size);
+            let res = Interpreter::new(Mode::Conditional).run_stream(scanner).unwrap();
+            assert_eq!(res, 26, "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "chunk size must be at least 1")]
+    fn stream_rejects_empty_chunks() {
+        Scanner::with_chunk_size("mul(1,2)".as_bytes(), Syntax::classic(), 0);
+    }
+
+    //
+    // extended instruction set
+    //
+
+    fn extended(operand_limit: OperandLimit) -> Syntax {
+        Syntax {
+            instruction_set: InstructionSet::Extended,
+            operand_limit,
+        }
+    }
+
+    fn run_extended(input: &str, operand_limit: OperandLimit) -> usize {
+        Interpreter::with_syntax(Mode::Conditional, extended(operand_limit)).run(input)
+    }
+
+    #[test]
+    fn extended_set_knows_add_sub_and_div() {
+        let input = "add(1,2)-sub(10,4)*div(9,2)";
+        assert_eq!(run_extended(input, OperandLimit::Strict), 3 + 6 + 4);
+    }
+
+    #[test]
+    fn classic_set_ignores_extended_instructions() {
+        let input = "add(1,2)mul(2,3)mul(add(1,2),3)";
+        assert_eq!(step1(input), 6);
+    }
+
+    #[test]
+    fn extended_set_supports_nested_calls() {
+        let input = "xmul(add(1,2),3)don't()add(1,1)do()div(mul(4,5),sub(7,2))";
+        assert_eq!(run_extended(input, OperandLimit::Strict), 9 + 4);
+    }
+
+    #[test]
+    fn nested_call_with_garbage_only_counts_the_inner_call() {
+        let input = "mul(xadd(1,2),3)";
+        assert_eq!(run_extended(input, OperandLimit::Strict), 3);
+    }
+
+    #[test]
+    fn failed_evaluations_are_skipped_and_traced() {
+        let input = "div(1,0)sub(1,2)add(2,2)";
+        let trace = Interpreter::with_syntax(Mode::Conditional, extended(OperandLimit::Strict)).trace(input);
+
+        let errors: Vec<Option<EvalError>> = trace.iter().map(|e| e.error).collect();
+        assert_eq!(errors, vec![Some(EvalError::DivisionByZero), Some(EvalError::Underflow), None]);
+        assert_eq!(trace[2].sum, 4);
+        assert_eq!(trace[0].to_string(), "     0  div(1,0)     enabled  sum=0 (division by zero)");
+    }
+
+    #[test]
+    fn strict_operands_have_at_most_3_digits() {
+        let input = "mul(1000,2)mul(999,2)";
+        assert_eq!(run_extended(input, OperandLimit::Strict), 1998);
+        assert_eq!(run_extended(input, OperandLimit::Unlimited), 3998);
+    }
+
+    #[test]
+    fn unlimited_operands_that_overflow_are_not_instructions() {
+        let input = "mul(99999999999999999999999,1)mul(2,2)";
+        assert_eq!(run_extended(input, OperandLimit::Unlimited), 4);
+    }
+
+    #[test]
+    fn deep_and_unterminated_nesting_is_not_an_instruction() {
+        let syntax = extended(OperandLimit::Strict);
+        let nested = |calls: usize| format!("{}add(1,1){}", "add(1,".repeat(calls - 1), ")".repeat(calls - 1));
+        let unterminated = "mul(".repeat(50_000);
+
+        for (input, expected) in [(nested(32), 33), (nested(40), 33), (unterminated, 0)] {
+            assert_eq!(Interpreter::with_syntax(Mode::Conditional, syntax).run(&input), expected);
+            for chunk_size in [64, 4096] {
+                let scanner = Scanner::with_chunk_size(input.as_bytes(), syntax, chunk_size);
+                let res = Interpreter::with_syntax(Mode::Conditional, syntax).run_stream(scanner).unwrap();
+                assert_eq!(res, expected, "chunk size {}", chunk_size);
+            }
+        }
+    }
+
+    #[test]
+    fn stream_handles_nested_calls_across_chunks() {
+        let input = "..mul(add(100,2),sub(9,6))..add(12345678,1)";
+        let syntax = extended(OperandLimit::Unlimited);
+        let expected = Interpreter::with_syntax(Mode::Conditional, syntax).run(input);
+
+        for chunk_size in 1..=8 {
+            let scanner = Scanner::with_chunk_size(input.as_bytes(), syntax, chunk_size);
+            let res = Interpreter::with_syntax(Mode::Conditional, syntax).run_stream(scanner).unwrap();
+            assert_eq!(res, expected);
+        }
+        assert_eq!(expected, 306 + 12345679);
+    }
 }
@@ -0,0 +1,77 @@
+use crate::instruction::{parse_partial, ParsedInstruction, Syntax};
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read};
+
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+// Reads instructions from any source without loading it whole. The bytes of an
+// instruction that can still be completed by the next chunk are carried over,
+// and offsets are reported from the beginning of the stream.
+pub struct Scanner<R: Read> {
+    reader: R,
+    chunk_size: usize,
+    syntax: Syntax,
+    buffer: Vec<u8>,
+    offset: usize,
+    ready: VecDeque<ParsedInstruction>,
+    eof: bool,
+}
+
+impl<R: Read> Scanner<R> {
+    pub fn new(reader: R, syntax: Syntax) -> Scanner<R> {
+        Self::with_chunk_size(reader, syntax, DEFAULT_CHUNK_SIZE)
+    }
+
+    // An empty read means the end of the stream, so chunks can't be empty.
+    pub fn with_chunk_size(reader: R, syntax: Syntax, chunk_size: usize) -> Scanner<R> {
+        assert!(chunk_size > 0, "chunk size must be at least 1");
+        Scanner {
+            reader,
+            chunk_size,
+            syntax,
+            buffer: Vec::with_capacity(chunk_size * 2),
+            offset: 0,
+            ready: VecDeque::new(),
+            eof: false,
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let filled = self.buffer.len();
+        self.buffer.resize(filled + self.chunk_size, 0);
+        let read = loop {
+            match self.reader.read(&mut self.buffer[filled..]) {
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                res => break res,
+            }
+        };
+        let read = read.inspect_err(|_| self.buffer.truncate(filled))?;
+        self.buffer.truncate(filled + read);
+        self.eof = read == 0;
+
+        let parsed = parse_partial(&self.buffer, self.eof, self.syntax);
+        let offset = self.offset;
+        self.ready.extend(parsed.instructions.into_iter().map(|mut p| {
+            p.start += offset;
+            p.end += offset;
+            p
+        }));
+        self.buffer.drain(..parsed.consumed);
+        self.offset += parsed.consumed;
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for Scanner<R> {
+    type Item = io::Result<ParsedInstruction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.ready.is_empty() && !self.eof {
+            if let Err(e) = self.fill() {
+                return Some(Err(e));
+            }
+        }
+        self.ready.pop_front().map(Ok)
+    }
+}
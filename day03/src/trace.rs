@@ -0,0 +1,85 @@
+use crate::instruction::{EvalError, Expr, Instruction, ParsedInstruction};
+use std::fmt;
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const MAGENTA: &str = "\x1b[35m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    pub parsed: ParsedInstruction,
+    pub enabled: bool,
+    pub error: Option<EvalError>,
+    pub sum: usize,
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Number(n) => write!(f, "{}", n),
+            Expr::Call(operator, e1, e2) => write!(f, "{}({},{})", operator.name(), e1, e2),
+        }
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::Overflow => write!(f, "overflow"),
+            EvalError::Underflow => write!(f, "underflow"),
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Eval(expr) => write!(f, "{}", expr),
+            Instruction::Do => write!(f, "do()"),
+            Instruction::Dont => write!(f, "don't()"),
+        }
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = if self.enabled { "enabled" } else { "disabled" };
+        write!(
+            f,
+            "{:>6}  {:<12} {:<8} sum={}",
+            self.parsed.start,
+            self.parsed.instruction.to_string(),
+            state,
+            self.sum
+        )?;
+        match self.error {
+            Some(error) => write!(f, " ({})", error),
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn highlight(input: &str, trace: &[TraceEntry]) -> String {
+    let mut res = String::with_capacity(input.len());
+    let mut last = 0;
+
+    for entry in trace {
+        let color = match entry.parsed.instruction {
+            Instruction::Eval(_) if entry.error.is_some() => MAGENTA,
+            Instruction::Eval(_) if entry.enabled => GREEN,
+            Instruction::Eval(_) => RED,
+            _ => YELLOW,
+        };
+        res.push_str(&input[last..entry.parsed.start]);
+        res.push_str(color);
+        res.push_str(&input[entry.parsed.start..entry.parsed.end]);
+        res.push_str(RESET);
+        last = entry.parsed.end;
+    }
+    res.push_str(&input[last..]);
+
+    res
+}
@@ -1,189 +1,309 @@
+use crate::pattern::{Pattern, PatternMatch};
+use crate::render::Cells;
+use crate::word_search::WordSearch;
+use std::fmt;
+use std::time::Instant;
+use unicode_segmentation::UnicodeSegmentation;
+
+mod pattern;
+mod render;
+mod word_search;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Direction {
+    East,
+    West,
+    South,
+    North,
+    NorthEast,
+    SouthEast,
+    SouthWest,
+    NorthWest,
+}
+
+const DIRECTIONS: [Direction; 8] = [
+    Direction::East,
+    Direction::West,
+    Direction::South,
+    Direction::North,
+    Direction::NorthEast,
+    Direction::SouthEast,
+    Direction::SouthWest,
+    Direction::NorthWest,
+];
+
+impl Direction {
+    pub fn delta(&self) -> (isize, isize) {
+        match self {
+            Direction::East => (0, 1),
+            Direction::West => (0, -1),
+            Direction::South => (1, 0),
+            Direction::North => (-1, 0),
+            Direction::NorthEast => (-1, 1),
+            Direction::SouthEast => (1, 1),
+            Direction::SouthWest => (1, -1),
+            Direction::NorthWest => (-1, -1),
+        }
+    }
+
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::East => Direction::West,
+            Direction::West => Direction::East,
+            Direction::South => Direction::North,
+            Direction::North => Direction::South,
+            Direction::NorthEast => Direction::SouthWest,
+            Direction::SouthEast => Direction::NorthWest,
+            Direction::SouthWest => Direction::NorthEast,
+            Direction::NorthWest => Direction::SouthEast,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct WordMatch {
+    pub word: String,
+    pub row: usize,
+    pub column: usize,
+    pub direction: Direction,
+}
+
+fn mas_cross() -> Pattern {
+    Pattern::from_str("M.S / .A. / M.S").unwrap().with_rotations()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatrixError {
+    Empty,
+    UnevenRow {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for MatrixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatrixError::Empty => write!(f, "the matrix has no rows"),
+            MatrixError::UnevenRow {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} has {} cells but the first row has {}",
+                row, found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MatrixError {}
+
+// Cells are grapheme clusters, so a letter with combining accents is a
+// single cell. With `wrapping` the grid is a torus: words and patterns can
+// cross an edge and continue on the opposite one.
 struct XmasMatrix {
-    matrix: Vec<Vec<char>>,
+    matrix: Vec<Vec<String>>,
+    // The cells row after row, when every one of them is a single byte.
+    // Comparing bytes is much faster than comparing a `String` per cell.
+    ascii: Option<Vec<u8>>,
     columns: usize,
     rows: usize,
+    wrap: bool,
 }
 
 impl XmasMatrix {
-    pub fn from_str(data: &str) -> XmasMatrix {
+    pub fn from_str(data: &str) -> Result<XmasMatrix, MatrixError> {
         let matrix = data
             .lines()
             .filter(|l| !l.is_empty())
-            .map(|l| l.chars().collect::<Vec<char>>())
-            .collect::<Vec<Vec<char>>>();
+            .map(|l| l.graphemes(true).map(String::from).collect::<Vec<String>>())
+            .collect::<Vec<Vec<String>>>();
+
+        let columns = matrix.first().ok_or(MatrixError::Empty)?.len();
+        if let Some((row, cells)) = matrix.iter().enumerate().find(|(_, r)| r.len() != columns) {
+            return Err(MatrixError::UnevenRow {
+                row,
+                expected: columns,
+                found: cells.len(),
+            });
+        }
 
-        let columns = matrix[0].len();
         let rows = matrix.len();
-        XmasMatrix {
+        let ascii = matrix
+            .iter()
+            .flatten()
+            .map(|cell| Some(cell.as_bytes()).filter(|b| b.len() == 1).map(|b| b[0]))
+            .collect();
+        Ok(XmasMatrix {
             matrix,
+            ascii,
             columns,
             rows,
-        }
+            wrap: false,
+        })
     }
 
-    pub fn count_xmas(&self) -> usize {
-        let x_positions = self.find_positions_of('X');
-        x_positions
-            .iter()
-            .map(|(row, column)| self.find_rest_of_xmas(*row, *column))
-            .sum()
+    pub fn wrapping(mut self) -> XmasMatrix {
+        self.wrap = true;
+        self
     }
 
-    pub fn count_mas_cross(&self) -> usize {
-        let x_positions = self.find_positions_of('A');
-        x_positions
-            .iter()
-            .map(|(row, column)| self.find_rest_of_mas_cross(*row, *column))
-            .sum()
+    pub fn count_xmas(&self) -> usize {
+        self.count_word("XMAS")
     }
 
-    fn find_rest_of_xmas(&self, row: usize, column: usize) -> usize {
-        self.west_to_east(row, column)
-            + self.east_to_west(row, column)
-            + self.north_to_south(row, column)
-            + self.south_to_north(row, column)
-            + self.sw_to_ne(row, column)
-            + self.nw_to_se(row, column)
-            + self.ne_to_sw(row, column)
-            + self.se_to_nw(row, column)
+    pub fn count_mas_cross(&self) -> usize {
+        self.count_pattern(&mas_cross())
     }
 
-    fn west_to_east(&self, row: usize, column: usize) -> usize {
-        let is_match = self.position_is(row, column + 1, 'M')
-            && self.position_is(row, column + 2, 'A')
-            && self.position_is(row, column + 3, 'S');
-        usize::from(is_match)
+    pub fn count_pattern(&self, pattern: &Pattern) -> usize {
+        self.find_pattern(pattern).len()
     }
 
-    fn east_to_west(&self, row: usize, column: usize) -> usize {
-        if column < 3 { return 0 }
-
-        let is_match = self.position_is(row, column - 1, 'M')
-            && self.position_is(row, column - 2, 'A')
-            && self.position_is(row, column - 3, 'S');
-        usize::from(is_match)
-    }
+    pub fn find_pattern(&self, pattern: &Pattern) -> Vec<PatternMatch> {
+        let mut res = vec![];
 
-    fn north_to_south(&self, row: usize, column: usize) -> usize {
-        let is_match = self.position_is(row + 1, column, 'M')
-            && self.position_is(row + 2, column, 'A')
-            && self.position_is(row + 3, column, 'S');
-        usize::from(is_match)
-    }
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                for variant in 0..pattern.variants() {
+                    if self.template_at(pattern.variant(variant), row, column) {
+                        res.push(PatternMatch { row, column, variant })
+                    }
+                }
+            }
+        }
 
-    fn south_to_north(&self, row: usize, column: usize) -> usize {
-        if row < 3 { return 0 }
-        let is_match = self.position_is(row - 1, column, 'M')
-            && self.position_is(row - 2, column, 'A')
-            && self.position_is(row - 3, column, 'S');
-        usize::from(is_match)
+        res
     }
 
-    fn sw_to_ne(&self, row: usize, column: usize) -> usize {
-        if row < 3 { return 0 }
+    fn template_at(&self, template: &[Vec<Option<String>>], row: usize, column: usize) -> bool {
+        let fits = self.wrap
+            || (template.len() + row <= self.rows
+                && template.first().map(|r| r.len()).unwrap_or(0) + column <= self.columns);
 
-        let is_match = self.position_is(row - 1, column + 1, 'M')
-            && self.position_is(row - 2, column + 2, 'A')
-            && self.position_is(row - 3, column + 3, 'S');
-        usize::from(is_match)
+        fits && template.iter().enumerate().all(|(row_offset, cells)| {
+            cells.iter().enumerate().all(|(column_offset, cell)| match cell {
+                Some(c) => self.position_is(
+                    (row + row_offset) as isize,
+                    (column + column_offset) as isize,
+                    c,
+                ),
+                None => true,
+            })
+        })
     }
 
-    fn nw_to_se(&self, row: usize, column: usize) -> usize {
-        let is_match = self.position_is(row + 1, column + 1, 'M')
-            && self.position_is(row + 2, column + 2, 'A')
-            && self.position_is(row + 3, column + 3, 'S');
-        usize::from(is_match)
+    pub fn count_word(&self, word: &str) -> usize {
+        self.find_word(word).len()
     }
 
-    fn ne_to_sw(&self, row: usize, column: usize) -> usize {
-        if column < 3 { return 0 }
-        let is_match = self.position_is(row + 1, column - 1, 'M')
-            && self.position_is(row + 2, column - 2, 'A')
-            && self.position_is(row + 3, column - 3, 'S');
-        usize::from(is_match)
-    }
+    pub fn find_word(&self, word: &str) -> Vec<WordMatch> {
+        let letters: Vec<&str> = word.graphemes(true).collect();
+        let Some(first) = letters.first() else {
+            return vec![];
+        };
 
-    fn se_to_nw(&self, row: usize, column: usize) -> usize {
-        if row < 3 { return 0 }
-        if column < 3 { return 0 }
-        let is_match = self.position_is(row - 1, column - 1, 'M')
-            && self.position_is(row - 2, column - 2, 'A')
-            && self.position_is(row - 3, column - 3, 'S');
-        usize::from(is_match)
-    }
+        let mut res = vec![];
 
+        for (row, column) in self.find_positions_of(first) {
+            for direction in DIRECTIONS {
+                if self.word_at(&letters, row, column, &direction)
+                    && self.traces_new_cells(row, column, &direction, letters.len())
+                {
+                    res.push(WordMatch {
+                        word: word.to_string(),
+                        row,
+                        column,
+                        direction,
+                    })
+                }
+            }
+        }
 
-    fn find_rest_of_mas_cross(&self, row: usize, column: usize) -> usize {
-        self.m_to_north(row, column)
-            + self.m_to_east(row, column)
-            + self.m_to_west(row, column)
-            + self.m_to_south(row, column)
+        res
     }
 
-    fn m_to_north(&self, row: usize, column: usize) -> usize {
-        if row < 1 { return 0 }
-        if column < 1 { return 0 }
-
-        let is_match = self.position_is(row - 1, column - 1, 'M')
-            && self.position_is(row - 1, column + 1, 'M')
-            && self.position_is(row + 1, column - 1, 'S')
-            && self.position_is(row + 1, column + 1, 'S');
-
-        is_match.into()
+    pub fn find_words(&self, words: &[&str]) -> Vec<WordMatch> {
+        words.iter().flat_map(|word| self.find_word(word)).collect()
     }
-    fn m_to_east(&self, row: usize, column: usize) -> usize {
-        if row < 1 { return 0 }
-        if column < 1 { return 0 }
 
-        let is_match = self.position_is(row + 1, column + 1, 'M')
-            && self.position_is(row - 1, column + 1, 'M')
-            && self.position_is(row - 1, column - 1, 'S')
-            && self.position_is(row + 1, column - 1, 'S');
+    fn word_at(&self, letters: &[&str], row: usize, column: usize, direction: &Direction) -> bool {
+        let (row_delta, column_delta) = direction.delta();
 
-        is_match.into()
+        letters.iter().enumerate().all(|(distance, letter)| {
+            let distance = distance as isize;
+            let row = row as isize + row_delta * distance;
+            let column = column as isize + column_delta * distance;
+            self.position_is(row, column, letter)
+        })
     }
-    fn m_to_west(&self, row: usize, column: usize) -> usize {
-        if row < 1 { return 0 }
-        if column < 1 { return 0 }
 
-        let is_match = self.position_is(row - 1, column - 1, 'M')
-            && self.position_is(row + 1, column - 1, 'M')
-            && self.position_is(row - 1, column + 1, 'S')
-            && self.position_is(row + 1, column + 1, 'S');
+    // On a torus with a side of 1 or 2 cells, different directions can go
+    // through the same cells or a word can go round and use a cell twice.
+    // Only the first direction, in `DIRECTIONS` order, tracing some cells
+    // counts. A one letter word is found in every direction, as without
+    // wrapping.
+    pub fn traces_new_cells(&self, row: usize, column: usize, direction: &Direction, len: usize) -> bool {
+        if len < 2 || !self.wrap {
+            return true;
+        }
+        let cells = |direction: &Direction| -> Vec<Option<(usize, usize)>> {
+            let (row_delta, column_delta) = direction.delta();
+            (0..len as isize)
+                .map(|distance| {
+                    self.position(
+                        row as isize + row_delta * distance,
+                        column as isize + column_delta * distance,
+                    )
+                })
+                .collect()
+        };
+
+        let traced = cells(direction);
+        let mut distinct = traced.clone();
+        distinct.sort();
+        distinct.dedup();
+        distinct.len() == traced.len()
+            && DIRECTIONS
+                .iter()
+                .take_while(|other| *other != direction)
+                .all(|other| cells(other) != traced)
+    }
+
+    fn position(&self, row: isize, column: isize) -> Option<(usize, usize)> {
+        if self.wrap {
+            let row = row.rem_euclid(self.rows as isize);
+            let column = column.rem_euclid(self.columns as isize);
+            return Some((row as usize, column as usize));
+        }
 
-        is_match.into()
+        let row = usize::try_from(row).ok().filter(|r| *r < self.rows)?;
+        let column = usize::try_from(column).ok().filter(|c| *c < self.columns)?;
+        Some((row, column))
     }
-    fn m_to_south(&self, row: usize, column: usize) -> usize {
-        if row < 1 { return 0 }
-        if column < 1 { return 0 }
-
-        let is_match = self.position_is(row + 1, column - 1, 'M')
-            && self.position_is(row + 1, column + 1, 'M')
-            && self.position_is(row - 1, column + 1, 'S')
-            && self.position_is(row - 1, column - 1, 'S');
 
-        is_match.into()
+    fn position_is(&self, row: isize, column: isize, target: &str) -> bool {
+        self.position(row, column)
+            .map(|(row, column)| self.cell_is(row, column, target))
+            .unwrap_or(false)
     }
 
-    fn position_is(&self, row: usize, column: usize, target: char) -> bool {
-        let x = self.matrix
-            .get(row)
-            .and_then(|inner| inner.get(column))
-            .map(|c| {
-                *c == target
-            })
-            .unwrap_or(false);
-
-        x
+    fn cell_is(&self, row: usize, column: usize, target: &str) -> bool {
+        match &self.ascii {
+            Some(bytes) => target.as_bytes() == [bytes[row * self.columns + column]],
+            None => self.matrix[row][column] == target,
+        }
     }
 
-    fn find_positions_of(&self, token: char) -> Vec<(usize, usize)> {
+    fn find_positions_of(&self, token: &str) -> Vec<(usize, usize)> {
         let mut res = vec![];
 
         for row in 0..self.rows {
             for column in 0..self.columns {
-                let cell = self.matrix[row][column];
-                if cell == token {
+                if self.cell_is(row, column, token) {
                     res.push((row, column))
                 }
             }
@@ -194,17 +314,125 @@ impl XmasMatrix {
 }
 
 fn step1(input: &str) -> usize {
-    let matrix = XmasMatrix::from_str(input);
+    let matrix = XmasMatrix::from_str(input).unwrap();
     matrix.count_xmas()
 }
 
 fn step2(input: &str) -> usize {
-    let matrix = XmasMatrix::from_str(input);
+    let matrix = XmasMatrix::from_str(input).unwrap();
     matrix.count_mas_cross()
 }
 
+fn bench(input: &str, times: usize) {
+    let tiled: String = (0..times)
+        .flat_map(|_| input.lines().filter(|l| !l.is_empty()))
+        .map(|line| format!("{}\n", line.repeat(times)))
+        .collect();
+    let matrix = XmasMatrix::from_str(&tiled).unwrap();
+    let words = ["XMAS", "SAMX", "MAS", "AXA", "XXMM"];
+    println!("grid: {}x{}, words: {:?}", matrix.rows, matrix.columns, words);
+
+    let start = Instant::now();
+    let probing = matrix.find_words(&words).len();
+    println!("neighbour probing: {} matches in {:?}", probing, start.elapsed());
+
+    let start = Instant::now();
+    let search = WordSearch::new(&matrix);
+    let extracted = start.elapsed();
+    let automaton = search.find_words(&words).len();
+    println!(
+        "aho-corasick: {} matches in {:?} ({:?} extracting lines)",
+        automaton,
+        start.elapsed(),
+        extracted
+    );
+}
+
+fn load(input: &str, args: &[String]) -> XmasMatrix {
+    let matrix = XmasMatrix::from_str(input).unwrap_or_else(|e| {
+        eprintln!("invalid input: {}", e);
+        std::process::exit(1)
+    });
+
+    if args.iter().any(|arg| arg == "--wrap") {
+        matrix.wrapping()
+    } else {
+        matrix
+    }
+}
+
 fn main() {
     let input = include_str!("../input.txt");
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.iter().any(|arg| arg == "--bench") {
+        bench(input, 10);
+        return;
+    }
+
+    if let Some(words) = args
+        .iter()
+        .position(|arg| arg == "--words")
+        .and_then(|index| args.get(index + 1))
+    {
+        let words: Vec<&str> = words.split(',').collect();
+        let matrix = load(input, &args);
+        let found = WordSearch::new(&matrix).find_words(&words);
+        for word in words {
+            println!("{}: {}", word, found.iter().filter(|m| m.word == word).count());
+        }
+        return;
+    }
+
+    if let Some(template) = args
+        .iter()
+        .position(|arg| arg == "--pattern")
+        .and_then(|index| args.get(index + 1))
+    {
+        let mut pattern = Pattern::from_str(template).unwrap_or_else(|e| {
+            eprintln!("invalid pattern: {}", e);
+            std::process::exit(1)
+        });
+        if args.iter().any(|arg| arg == "--rotate") {
+            pattern = pattern.with_rotations();
+        }
+        if args.iter().any(|arg| arg == "--mirror") {
+            pattern = pattern.with_mirrors();
+        }
+        println!("{}", load(input, &args).count_pattern(&pattern));
+        return;
+    }
+
+    if args.iter().any(|arg| arg == "--render") {
+        let matrix = load(input, &args);
+        let render = |matches: &[Cells]| {
+            if args.iter().any(|arg| arg == "--color") {
+                matrix.render_colored(matches)
+            } else {
+                matrix.render(matches)
+            }
+        };
+
+        let xmas: Vec<Cells> = matrix.find_word("XMAS").iter().map(|m| matrix.word_cells(m)).collect();
+        println!("step1:\n{}", render(&xmas));
+
+        let cross = mas_cross();
+        let crosses: Vec<Cells> = matrix
+            .find_pattern(&cross)
+            .iter()
+            .map(|m| matrix.pattern_cells(&cross, m))
+            .collect();
+        println!("step2:\n{}", render(&crosses));
+        return;
+    }
+
+    if args.iter().any(|arg| arg == "--wrap") {
+        let matrix = load(input, &args);
+        println!("step1: {}", matrix.count_xmas());
+        println!("step2: {}", matrix.count_mas_cross());
+        return;
+    }
+
     let res1 = step1(input);
     let res2 = step2(input);
 
@@ -215,6 +443,7 @@ fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::pattern::PatternError;
 
     #[test]
     fn can_count_horizontal() {
@@ -253,6 +482,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_concat)]
     fn can_count_horizontal_backward() {
         let input = concat!("SAMX\n",);
         assert_eq!(step1(input), 1);
@@ -344,6 +574,47 @@ mod tests {
         assert_eq!(step1(input), 18);
     }
 
+    #[test]
+    fn find_word_returns_position_and_direction() {
+        let input = concat!("XMAS\n", "M...\n", "A...\n", "S...\n");
+        let matrix = XmasMatrix::from_str(input).unwrap();
+
+        let found: Vec<(usize, usize, Direction)> = matrix
+            .find_word("XMAS")
+            .into_iter()
+            .map(|m| (m.row, m.column, m.direction))
+            .collect();
+        assert_eq!(found, vec![(0, 0, Direction::East), (0, 0, Direction::South)]);
+    }
+
+    #[test]
+    fn count_word_works_for_any_length() {
+        let input = concat!("ABCDEF\n", "B.....\n", "FEDCBA\n");
+        let matrix = XmasMatrix::from_str(input).unwrap();
+
+        assert_eq!(matrix.count_word("ABCDEF"), 2);
+        assert_eq!(matrix.count_word("AB"), 3);
+        assert_eq!(matrix.count_word("A"), 2 * 8);
+        assert_eq!(matrix.count_word(""), 0);
+        assert_eq!(matrix.count_word("ABCDEFG"), 0);
+    }
+
+    #[test]
+    fn find_words_searches_a_dictionary() {
+        let input = concat!("CAT.\n", "O...\n", "W...\n");
+        let matrix = XmasMatrix::from_str(input).unwrap();
+
+        let found: Vec<(String, Direction)> = matrix
+            .find_words(&["CAT", "COW", "DOG"])
+            .into_iter()
+            .map(|m| (m.word, m.direction))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("CAT".to_string(), Direction::East), ("COW".to_string(), Direction::South)]
+        );
+    }
+
     //
     // Step 2
     //
@@ -387,4 +658,299 @@ mod tests {
         );
         assert_eq!(step2(input), 1)
     }
+
+    #[test]
+    fn step_2_example_input() {
+        let input = concat!(
+            "MMMSXXMASM\n",
+            "MSAMXMSMSA\n",
+            "AMXSXMAAMM\n",
+            "MSAMASMSMX\n",
+            "XMASAMXAMM\n",
+            "XXAMMXXAMA\n",
+            "SMSMSASXSS\n",
+            "SAXAMASAAA\n",
+            "MAMMMXMMMM\n",
+            "MXMXAXMASX\n"
+        );
+
+        assert_eq!(step2(input), 9);
+    }
+
+    #[test]
+    fn pattern_without_rotation_only_matches_its_orientation() {
+        let input = concat!("M.S.S.M\n", ".A...A.\n", "M.S.S.M\n");
+        let matrix = XmasMatrix::from_str(input).unwrap();
+
+        let pattern = Pattern::from_str("M.S / .A. / M.S").unwrap();
+        let found = matrix.find_pattern(&pattern);
+        assert_eq!(found, vec![PatternMatch { row: 0, column: 0, variant: 0 }]);
+        assert_eq!(matrix.count_pattern(&pattern.with_rotations()), 2);
+    }
+
+    #[test]
+    fn symmetric_patterns_are_not_counted_twice() {
+        let matrix = XmasMatrix::from_str(concat!(".M.\n", "MAM\n", ".M.\n")).unwrap();
+
+        let plus = Pattern::from_str(".M. / MAM / .M.").unwrap();
+        assert_eq!(plus.clone().with_rotations().with_mirrors().variants(), 1);
+        assert_eq!(matrix.count_pattern(&plus.with_rotations().with_mirrors()), 1);
+    }
+
+    #[test]
+    fn mirrors_add_the_reflected_shape() {
+        let matrix = XmasMatrix::from_str(concat!("AB\n", "C.\n", "BA\n", ".C\n")).unwrap();
+
+        let corner = Pattern::from_str("AB / C.").unwrap();
+        assert_eq!(matrix.count_pattern(&corner), 1);
+        assert_eq!(matrix.count_pattern(&corner.clone().with_mirrors()), 2);
+        assert_eq!(corner.with_rotations().with_mirrors().variants(), 8);
+    }
+
+    #[test]
+    fn pattern_must_fit_inside_the_matrix() {
+        let matrix = XmasMatrix::from_str("AB\n").unwrap();
+
+        assert_eq!(matrix.count_pattern(&Pattern::from_str("AB / ..").unwrap()), 0);
+    }
+
+    #[test]
+    fn patterns_must_be_rectangular_and_not_empty() {
+        assert_eq!(Pattern::from_str(" / "), Err(PatternError::Empty));
+        assert_eq!(
+            Pattern::from_str("M.S / .A / M.S"),
+            Err(PatternError::UnevenRow {
+                row: 1,
+                expected: 3,
+                found: 2
+            })
+        );
+    }
+
+    fn sorted(mut matches: Vec<WordMatch>) -> Vec<WordMatch> {
+        matches.sort();
+        matches
+    }
+
+    #[test]
+    fn word_search_finds_the_same_as_probing() {
+        let input = concat!(
+            "MMMSXXMASM\n",
+            "MSAMXMSMSA\n",
+            "AMXSXMAAMM\n",
+            "MSAMASMSMX\n",
+            "XMASAMXAMM\n",
+            "XXAMMXXAMA\n",
+            "SMSMSASXSS\n",
+            "SAXAMASAAA\n",
+            "MAMMMXMMMM\n",
+            "MXMXAXMASX\n"
+        );
+        let matrix = XmasMatrix::from_str(input).unwrap();
+        let words = ["XMAS", "MAS", "X", "AMA", "MMMSXXMASMX"];
+
+        let found = WordSearch::new(&matrix).find_words(&words);
+        assert_eq!(found.iter().filter(|m| m.word == "XMAS").count(), 18);
+        assert_eq!(sorted(found), sorted(matrix.find_words(&words)));
+    }
+
+    #[test]
+    fn word_search_covers_non_square_matrices() {
+        let inputs = [
+            concat!("XMASX\n", "MMAMM\n", "AAAAA\n"),
+            concat!("XMA\n", "MMS\n", "AAA\n", "SSS\n", "XMA\n"),
+            "XMAS\n",
+            concat!("X\n", "M\n", "A\n", "S\n"),
+        ];
+
+        for input in inputs {
+            let matrix = XmasMatrix::from_str(input).unwrap();
+            let words = ["XMAS", "MA", "S"];
+            assert_eq!(
+                sorted(WordSearch::new(&matrix).find_words(&words)),
+                sorted(matrix.find_words(&words)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn word_search_ignores_empty_words() {
+        let matrix = XmasMatrix::from_str("XMAS\n").unwrap();
+
+        assert_eq!(WordSearch::new(&matrix).find_words(&["", "XMAS"]).len(), 1);
+        assert!(WordSearch::new(&matrix).find_words(&[]).is_empty());
+    }
+
+    #[test]
+    fn render_only_shows_matched_cells() {
+        let input = concat!(
+            "MMMSXXMASM\n",
+            "MSAMXMSMSA\n",
+            "AMXSXMAAMM\n",
+            "MSAMASMSMX\n",
+            "XMASAMXAMM\n",
+            "XXAMMXXAMA\n",
+            "SMSMSASXSS\n",
+            "SAXAMASAAA\n",
+            "MAMMMXMMMM\n",
+            "MXMXAXMASX\n"
+        );
+        let matrix = XmasMatrix::from_str(input).unwrap();
+        let matches: Vec<Cells> = matrix.find_word("XMAS").iter().map(|m| matrix.word_cells(m)).collect();
+
+        assert_eq!(
+            matrix.render(&matches),
+            concat!(
+                "....XXMAS.\n",
+                ".SAMXMS...\n",
+                "...S..A...\n",
+                "..A.A.MS.X\n",
+                "XMASAMX.MM\n",
+                "X.....XA.A\n",
+                "S.S.S.S.SS\n",
+                ".A.A.A.A.A\n",
+                "..M.M.M.MM\n",
+                ".X.X.XMASX\n"
+            )
+        );
+    }
+
+    #[test]
+    fn render_pattern_matches_skips_wildcards() {
+        let matrix = XmasMatrix::from_str(concat!("MXS\n", "XAX\n", "MXS\n", "XXX\n")).unwrap();
+        let cross = mas_cross();
+        let matches: Vec<Cells> = matrix
+            .find_pattern(&cross)
+            .iter()
+            .map(|m| matrix.pattern_cells(&cross, m))
+            .collect();
+
+        assert_eq!(matrix.render(&matches), concat!("M.S\n", ".A.\n", "M.S\n", "...\n"));
+    }
+
+    #[test]
+    fn render_colored_gives_every_match_its_own_color() {
+        let matrix = XmasMatrix::from_str("XMASAMX\n").unwrap();
+        let matches: Vec<Cells> = matrix.find_word("XMAS").iter().map(|m| matrix.word_cells(m)).collect();
+
+        assert_eq!(
+            matrix.render_colored(&matches),
+            concat!(
+                "\x1b[31mX\x1b[0m\x1b[31mM\x1b[0m\x1b[31mA\x1b[0m\x1b[31mS\x1b[0m",
+                "\x1b[32mA\x1b[0m\x1b[32mM\x1b[0m\x1b[32mX\x1b[0m\n"
+            )
+        );
+    }
+
+    //
+    // Validation, wrapping and unicode
+    //
+
+    #[test]
+    fn rows_of_different_length_are_an_error() {
+        let error = XmasMatrix::from_str(concat!("XMAS\n", "XMA\n")).err();
+
+        assert_eq!(
+            error,
+            Some(MatrixError::UnevenRow {
+                row: 1,
+                expected: 4,
+                found: 3
+            })
+        );
+        assert_eq!(error.unwrap().to_string(), "row 1 has 3 cells but the first row has 4");
+        assert_eq!(XmasMatrix::from_str("\n").err(), Some(MatrixError::Empty));
+    }
+
+    #[test]
+    fn wrapping_finds_words_crossing_the_edges() {
+        let input = concat!("ASXM\n", "....\n", "....\n", "....\n");
+        let matrix = XmasMatrix::from_str(input).unwrap();
+        assert_eq!(matrix.count_xmas(), 0);
+
+        let matrix = matrix.wrapping();
+        let found: Vec<(usize, usize, Direction)> = matrix
+            .find_word("XMAS")
+            .into_iter()
+            .map(|m| (m.row, m.column, m.direction))
+            .collect();
+        assert_eq!(found, vec![(0, 2, Direction::East)]);
+    }
+
+    #[test]
+    fn wrapping_finds_diagonals_crossing_the_edges() {
+        let input = concat!("A..\n", ".S.\n", ".X.\n", "..M\n");
+        let matrix = XmasMatrix::from_str(input).unwrap().wrapping();
+
+        assert_eq!(matrix.count_xmas(), 1);
+        assert_eq!(matrix.find_word("XMAS")[0].direction, Direction::SouthEast);
+    }
+
+    #[test]
+    fn wrapping_patterns_can_cross_the_edges() {
+        let input = concat!("S.M\n", "...\n", "S.M\n", ".A.\n");
+        let matrix = XmasMatrix::from_str(input).unwrap();
+        assert_eq!(matrix.count_mas_cross(), 0);
+
+        let matrix = matrix.wrapping();
+        assert_eq!(matrix.count_mas_cross(), 1);
+        let found = matrix.find_pattern(&mas_cross());
+        let cells = matrix.pattern_cells(&mas_cross(), &found[0]);
+        assert_eq!(matrix.render(&[cells]), concat!("S.M\n", "...\n", "S.M\n", ".A.\n"));
+    }
+
+    #[test]
+    fn word_search_wraps_like_probing() {
+        let inputs = [
+            concat!("ASXM\n", "MASX\n", "XMAS\n"),
+            concat!("XMA\n", "SXM\n", "ASX\n", "MAS\n", "XMA\n"),
+            "SX\n",
+            "XMAS\n",
+        ];
+
+        for input in inputs {
+            let matrix = XmasMatrix::from_str(input).unwrap().wrapping();
+            let words = ["XMAS", "SXMASXMAS", "MA"];
+            assert_eq!(
+                sorted(WordSearch::new(&matrix).find_words(&words)),
+                sorted(matrix.find_words(&words)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn small_torus_counts_each_word_once() {
+        // On one row going north or south stays on the row, so the diagonals
+        // trace the same cells as east.
+        let matrix = XmasMatrix::from_str("XMAS\n").unwrap().wrapping();
+        let found = matrix.find_word("XMAS");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].direction, Direction::East);
+        assert_eq!(WordSearch::new(&matrix).find_words(&["XMAS"]), found);
+
+        // On two rows and one column every direction but east and west goes to
+        // the other cell, and going on comes back to the first one.
+        let matrix = XmasMatrix::from_str("X\nM\n").unwrap().wrapping();
+        for (word, count) in [("XM", 1), ("MX", 1), ("X", 8), ("XMX", 0), ("XX", 0)] {
+            assert_eq!(matrix.count_word(word), count, "{}", word);
+            assert_eq!(WordSearch::new(&matrix).find_words(&[word]).len(), count, "{}", word);
+        }
+    }
+
+    #[test]
+    fn cells_are_graphemes() {
+        // "é" written as "e" followed by a combining accent is a single cell.
+        let input = concat!("CAFE\u{301}\n", "ÅBCD\n");
+        let matrix = XmasMatrix::from_str(input).unwrap();
+
+        assert_eq!(matrix.columns, 4);
+        assert_eq!(matrix.count_word("CAFE\u{301}"), 1);
+        assert_eq!(matrix.count_word("CAFE"), 0);
+        assert_eq!(matrix.count_word("CB"), 2);
+        assert_eq!(WordSearch::new(&matrix).find_words(&["CAFE\u{301}", "CAFE", "ÅA"]).len(), 2);
+    }
 }
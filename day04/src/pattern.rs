@@ -0,0 +1,132 @@
+use std::fmt;
+use unicode_segmentation::UnicodeSegmentation;
+
+pub const WILDCARD: &str = ".";
+
+type Template = Vec<Vec<Option<String>>>;
+
+// A small 2D template where `.` matches any cell. Rows are separated by new
+// lines or by `/`, so `M.S / .A. / M.S` is the X-MAS cross.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    variants: Vec<Template>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternMatch {
+    pub row: usize,
+    pub column: usize,
+    pub variant: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternError {
+    Empty,
+    UnevenRow {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::Empty => write!(f, "the pattern has no rows"),
+            PatternError::UnevenRow {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "pattern row {} has {} cells but the first row has {}",
+                row, found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+impl Pattern {
+    pub fn from_str(template: &str) -> Result<Pattern, PatternError> {
+        let template: Template = template
+            .split(['\n', '/'])
+            .map(|row| row.trim())
+            .filter(|row| !row.is_empty())
+            .map(|row| {
+                row.graphemes(true)
+                    .map(|c| if c == WILDCARD { None } else { Some(c.to_string()) })
+                    .collect()
+            })
+            .collect();
+
+        let columns = template.first().ok_or(PatternError::Empty)?.len();
+        if let Some((row, cells)) = template.iter().enumerate().find(|(_, r)| r.len() != columns) {
+            return Err(PatternError::UnevenRow {
+                row,
+                expected: columns,
+                found: cells.len(),
+            });
+        }
+
+        Ok(Pattern {
+            variants: vec![template],
+        })
+    }
+
+    pub fn with_rotations(self) -> Pattern {
+        self.extended_with(|template| {
+            let mut res = vec![];
+            let mut current = rotate(template);
+            for _ in 0..3 {
+                res.push(current.clone());
+                current = rotate(&current);
+            }
+            res
+        })
+    }
+
+    pub fn with_mirrors(self) -> Pattern {
+        self.extended_with(|template| vec![mirror(template)])
+    }
+
+    pub fn variants(&self) -> usize {
+        self.variants.len()
+    }
+
+    pub fn variant(&self, index: usize) -> &[Vec<Option<String>>] {
+        &self.variants[index]
+    }
+
+    // Symmetric templates produce the same variant more than once, and a shape
+    // must only be counted once per position.
+    fn extended_with(self, transform: impl Fn(&Template) -> Vec<Template>) -> Pattern {
+        let mut variants = self.variants.clone();
+        for template in &self.variants {
+            for new in transform(template) {
+                if !variants.contains(&new) {
+                    variants.push(new);
+                }
+            }
+        }
+        Pattern { variants }
+    }
+}
+
+// Clockwise.
+fn rotate(template: &Template) -> Template {
+    let rows = template.len();
+    let columns = template.first().map(|row| row.len()).unwrap_or(0);
+
+    (0..columns)
+        .map(|column| (0..rows).rev().map(|row| template[row][column].clone()).collect())
+        .collect()
+}
+
+fn mirror(template: &Template) -> Template {
+    template
+        .iter()
+        .map(|row| row.iter().rev().cloned().collect())
+        .collect()
+}
@@ -0,0 +1,75 @@
+use crate::pattern::{Pattern, PatternMatch};
+use crate::{WordMatch, XmasMatrix};
+use unicode_segmentation::UnicodeSegmentation;
+
+const COLORS: [&str; 6] = [
+    "\x1b[31m", "\x1b[32m", "\x1b[33m", "\x1b[34m", "\x1b[35m", "\x1b[36m",
+];
+const RESET: &str = "\x1b[0m";
+const HIDDEN: char = '.';
+
+pub type Cells = Vec<(usize, usize)>;
+
+impl XmasMatrix {
+    pub fn word_cells(&self, found: &WordMatch) -> Cells {
+        let (row_delta, column_delta) = found.direction.delta();
+        (0..found.word.graphemes(true).count() as isize)
+            .filter_map(|distance| {
+                self.position(
+                    found.row as isize + row_delta * distance,
+                    found.column as isize + column_delta * distance,
+                )
+            })
+            .collect()
+    }
+
+    pub fn pattern_cells(&self, pattern: &Pattern, found: &PatternMatch) -> Cells {
+        let mut res = vec![];
+        for (row_offset, cells) in pattern.variant(found.variant).iter().enumerate() {
+            for (column_offset, cell) in cells.iter().enumerate() {
+                if cell.is_none() {
+                    continue;
+                }
+                let row = (found.row + row_offset) as isize;
+                let column = (found.column + column_offset) as isize;
+                res.extend(self.position(row, column));
+            }
+        }
+        res
+    }
+
+    // Only the cells of some match are shown, the rest are replaced by `.`
+    // like in the puzzle examples.
+    pub fn render(&self, matches: &[Cells]) -> String {
+        self.render_with(matches, |c, _| c.to_string())
+    }
+
+    // Same as `render`, but every match gets its own color. A cell shared by
+    // several matches keeps the color of the first one.
+    pub fn render_colored(&self, matches: &[Cells]) -> String {
+        self.render_with(matches, |c, index| {
+            format!("{}{}{}", COLORS[index % COLORS.len()], c, RESET)
+        })
+    }
+
+    fn render_with(&self, matches: &[Cells], show: impl Fn(&str, usize) -> String) -> String {
+        let mut owner: Vec<Vec<Option<usize>>> = vec![vec![None; self.columns]; self.rows];
+        for (index, cells) in matches.iter().enumerate() {
+            for (row, column) in cells {
+                owner[*row][*column].get_or_insert(index);
+            }
+        }
+
+        let mut res = String::new();
+        for (cells, owners) in self.matrix.iter().zip(owner) {
+            for (cell, owner) in cells.iter().zip(owners) {
+                match owner {
+                    Some(index) => res.push_str(&show(cell, index)),
+                    None => res.push(HIDDEN),
+                }
+            }
+            res.push('\n');
+        }
+        res
+    }
+}
@@ -0,0 +1,201 @@
+use crate::{Direction, WordMatch, XmasMatrix};
+use aho_corasick::AhoCorasick;
+use std::borrow::Cow;
+use unicode_segmentation::UnicodeSegmentation;
+
+// The cells of a line are not stored: the one at `index` is `index` steps
+// from `start` in `direction`.
+struct Line {
+    start: (usize, usize),
+    direction: Direction,
+    len: usize,
+    text: String,
+    // Byte offset in `text` where each cell starts, unless every cell is a
+    // single byte.
+    offsets: Option<Vec<usize>>,
+}
+
+// Every row, column and diagonal of the matrix as a string, read in one
+// direction only: the words are searched reversed as well, so that a whole
+// dictionary is found with one automaton pass over each line.
+pub struct WordSearch<'a> {
+    matrix: &'a XmasMatrix,
+    lines: Vec<Line>,
+}
+
+impl Line {
+    fn cell(&self, matrix: &XmasMatrix, index: usize) -> (usize, usize) {
+        let (row_delta, column_delta) = self.direction.delta();
+        let index = (index % self.len) as isize;
+        matrix
+            .position(
+                self.start.0 as isize + row_delta * index,
+                self.start.1 as isize + column_delta * index,
+            )
+            .unwrap()
+    }
+
+    fn offset(&self, index: usize) -> usize {
+        match &self.offsets {
+            Some(offsets) => offsets[index],
+            None => index,
+        }
+    }
+
+    // A line of a wrapping matrix is a cycle. It is searched with its first
+    // `extra` cells appended again, so words crossing the end are found, but
+    // only matches starting on the original cells are reported.
+    fn haystack(&self, cyclic: bool, extra: usize) -> (Cow<'_, str>, Cow<'_, Option<Vec<usize>>>) {
+        if !cyclic {
+            return (Cow::Borrowed(&self.text), Cow::Borrowed(&self.offsets));
+        }
+
+        let mut text = self.text.clone();
+        let mut offsets = self.offsets.clone();
+        for index in 0..extra {
+            let index = index % self.len;
+            let end = if index + 1 < self.len {
+                self.offset(index + 1)
+            } else {
+                self.text.len()
+            };
+            if let Some(offsets) = &mut offsets {
+                offsets.push(text.len());
+            }
+            text.push_str(&self.text[self.offset(index)..end]);
+        }
+        (Cow::Owned(text), Cow::Owned(offsets))
+    }
+}
+
+impl WordSearch<'_> {
+    pub fn new(matrix: &XmasMatrix) -> WordSearch<'_> {
+        let mut lines = vec![];
+        for direction in [
+            Direction::East,
+            Direction::South,
+            Direction::SouthEast,
+            Direction::SouthWest,
+        ] {
+            lines.extend(lines_going(matrix, direction));
+        }
+
+        WordSearch { matrix, lines }
+    }
+
+    pub fn find_words(&self, words: &[&str]) -> Vec<WordMatch> {
+        let words: Vec<&str> = words.iter().filter(|w| !w.is_empty()).cloned().collect();
+        if words.is_empty() {
+            return vec![];
+        }
+        let lengths: Vec<usize> = words.iter().map(|w| w.graphemes(true).count()).collect();
+        let reversed: Vec<String> = words
+            .iter()
+            .map(|w| w.graphemes(true).rev().collect())
+            .collect();
+        // Pattern `i` is `words[i]` and pattern `words.len() + i` is it reversed.
+        let patterns: Vec<&str> = words
+            .iter()
+            .cloned()
+            .chain(reversed.iter().map(|w| w.as_str()))
+            .collect();
+        let automaton = AhoCorasick::new(&patterns).unwrap();
+        let longest = lengths.iter().max().cloned().unwrap_or(0);
+
+        let mut res = vec![];
+        for line in &self.lines {
+            let (text, offsets) = line.haystack(self.matrix.wrap, longest - 1);
+            for found in automaton.find_overlapping_iter(text.as_ref()) {
+                // Matches that start or end inside a grapheme are not words of the grid.
+                let index = match offsets.as_ref() {
+                    Some(offsets) => {
+                        let Ok(index) = offsets.binary_search(&found.start()) else {
+                            continue;
+                        };
+                        if found.end() != text.len() && offsets.binary_search(&found.end()).is_err()
+                        {
+                            continue;
+                        }
+                        index
+                    }
+                    None => found.start(),
+                };
+                if index >= line.len {
+                    continue;
+                }
+
+                let pattern = found.pattern().as_usize();
+                let (word, start, direction) = match pattern.checked_sub(words.len()) {
+                    None => (pattern, index, line.direction),
+                    Some(word) => (word, index + lengths[word] - 1, line.direction.opposite()),
+                };
+                let (row, column) = line.cell(self.matrix, start);
+                if !self
+                    .matrix
+                    .traces_new_cells(row, column, &direction, lengths[word])
+                {
+                    continue;
+                }
+                res.push(WordMatch {
+                    word: words[word].to_string(),
+                    row,
+                    column,
+                    direction,
+                });
+            }
+        }
+
+        res
+    }
+}
+
+// Every line going in `direction`. Without wrapping a line starts where the
+// previous cell would fall outside of the matrix; with wrapping the lines are
+// the cycles obtained by walking from every not yet visited cell.
+fn lines_going(matrix: &XmasMatrix, direction: Direction) -> Vec<Line> {
+    let (row_delta, column_delta) = direction.delta();
+    let next = |(row, column): (usize, usize)| {
+        matrix.position(row as isize + row_delta, column as isize + column_delta)
+    };
+    let previous = |(row, column): (usize, usize)| {
+        matrix.position(row as isize - row_delta, column as isize - column_delta)
+    };
+
+    let ascii = &matrix.ascii;
+    let mut visited = vec![vec![false; matrix.columns]; matrix.rows];
+    let mut res = vec![];
+
+    for row in 0..matrix.rows {
+        for column in 0..matrix.columns {
+            if visited[row][column] || (!matrix.wrap && previous((row, column)).is_some()) {
+                continue;
+            }
+
+            let mut text = String::new();
+            let mut offsets = vec![];
+            let mut len = 0;
+            let mut current = Some((row, column));
+            while let Some((r, c)) = current.filter(|(r, c)| !visited[*r][*c]) {
+                visited[r][c] = true;
+                match ascii {
+                    Some(bytes) => text.push(bytes[r * matrix.columns + c] as char),
+                    None => {
+                        offsets.push(text.len());
+                        text.push_str(&matrix.matrix[r][c]);
+                    }
+                }
+                len += 1;
+                current = next((r, c));
+            }
+            res.push(Line {
+                start: (row, column),
+                direction,
+                len,
+                offsets: Some(offsets).filter(|_| ascii.is_none()),
+                text,
+            });
+        }
+    }
+
+    res
+}
@@ -0,0 +1,224 @@
+use crate::{SafetyManual, UnfixableChapter};
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChapterStatus {
+    Ordered { middle: usize },
+    Fixed { middle: usize },
+    Unfixable(UnfixableChapter),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    AddRule(usize, usize),
+    RemoveRule(usize, usize),
+    AddChapter(Vec<usize>),
+    RemoveChapter(usize),
+}
+
+// A manual whose chapters keep their status and whose answers are kept up to
+// date as rules and chapters change. A rule only affects the chapters holding
+// both of its pages, so those are the only ones evaluated again.
+pub struct IncrementalManual {
+    manual: SafetyManual,
+    statuses: Vec<ChapterStatus>,
+    // Indexes of the chapters where every page appears.
+    chapters_by_page: HashMap<usize, BTreeSet<usize>>,
+    ordered_sum: usize,
+    fixed_sum: usize,
+    unfixable: usize,
+}
+
+impl Command {
+    // `a|b` or `+a|b` adds a rule and `-a|b` removes it, `1,2,3` or `+1,2,3`
+    // adds a chapter and `-index` removes the chapter at that index.
+    pub fn from_str(line: &str) -> Option<Command> {
+        let line = line.trim();
+        let (remove, body) = match line.strip_prefix('-') {
+            Some(body) => (true, body),
+            None => (false, line.strip_prefix('+').unwrap_or(line)),
+        };
+
+        if let Some((page, other)) = body.split_once('|') {
+            let page = page.trim().parse().ok()?;
+            let other = other.trim().parse().ok()?;
+            return Some(match remove {
+                true => Command::RemoveRule(page, other),
+                false => Command::AddRule(page, other),
+            });
+        }
+        if remove {
+            return body.trim().parse().ok().map(Command::RemoveChapter);
+        }
+        body.split(',')
+            .map(|chunk| chunk.trim().parse().ok())
+            .collect::<Option<Vec<usize>>>()
+            .map(Command::AddChapter)
+    }
+}
+
+impl IncrementalManual {
+    pub fn new(manual: SafetyManual) -> IncrementalManual {
+        let SafetyManual { rules, chapters } = manual;
+        let mut res = IncrementalManual {
+            manual: SafetyManual {
+                rules,
+                chapters: vec![],
+            },
+            statuses: vec![],
+            chapters_by_page: HashMap::new(),
+            ordered_sum: 0,
+            fixed_sum: 0,
+            unfixable: 0,
+        };
+        for chapter in chapters {
+            res.add_chapter(chapter);
+        }
+        res
+    }
+
+    pub fn manual(&self) -> &SafetyManual {
+        &self.manual
+    }
+
+    pub fn status(&self, chapter: usize) -> &ChapterStatus {
+        &self.statuses[chapter]
+    }
+
+    // Answer of step 1.
+    pub fn ordered_sum(&self) -> usize {
+        self.ordered_sum
+    }
+
+    // Answer of step 2, or the first chapter that cannot be fixed.
+    pub fn fixed_sum(&self) -> Result<usize, UnfixableChapter> {
+        if self.unfixable == 0 {
+            return Ok(self.fixed_sum);
+        }
+        match self
+            .statuses
+            .iter()
+            .find(|s| matches!(s, ChapterStatus::Unfixable(_)))
+        {
+            Some(ChapterStatus::Unfixable(e)) => Err(e.clone()),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn apply(&mut self, command: Command) -> bool {
+        match command {
+            Command::AddRule(page, other) => self.add_rule(page, other),
+            Command::RemoveRule(page, other) => self.remove_rule(page, other),
+            Command::AddChapter(chapter) => {
+                self.add_chapter(chapter);
+                true
+            }
+            Command::RemoveChapter(index) => self.remove_chapter(index).is_some(),
+        }
+    }
+
+    // Returns false if the rule already existed.
+    pub fn add_rule(&mut self, page: usize, other: usize) -> bool {
+        if self.manual.must_precede(page, other) {
+            return false;
+        }
+        self.manual.rules.entry(page).or_default().push(other);
+        self.reevaluate(page, other);
+        true
+    }
+
+    // Returns false if there was no such rule.
+    pub fn remove_rule(&mut self, page: usize, other: usize) -> bool {
+        let Some(afterwards) = self.manual.rules.get_mut(&page) else {
+            return false;
+        };
+        let before = afterwards.len();
+        afterwards.retain(|p| *p != other);
+        if afterwards.len() == before {
+            return false;
+        }
+        self.reevaluate(page, other);
+        true
+    }
+
+    // Returns the index of the new chapter.
+    pub fn add_chapter(&mut self, chapter: Vec<usize>) -> usize {
+        let index = self.manual.chapters.len();
+        for page in &chapter {
+            self.chapters_by_page
+                .entry(*page)
+                .or_default()
+                .insert(index);
+        }
+        let status = self.evaluate(&chapter);
+        self.count(&status, true);
+        self.statuses.push(status);
+        self.manual.chapters.push(chapter);
+        index
+    }
+
+    // Later chapters move one index down, like in a `Vec`.
+    pub fn remove_chapter(&mut self, index: usize) -> Option<Vec<usize>> {
+        if index >= self.manual.chapters.len() {
+            return None;
+        }
+        let chapter = self.manual.chapters.remove(index);
+        let status = self.statuses.remove(index);
+        self.count(&status, false);
+
+        for indexes in self.chapters_by_page.values_mut() {
+            *indexes = indexes
+                .iter()
+                .filter(|i| **i != index)
+                .map(|i| if *i > index { i - 1 } else { *i })
+                .collect();
+        }
+        self.chapters_by_page
+            .retain(|_, indexes| !indexes.is_empty());
+        Some(chapter)
+    }
+
+    fn reevaluate(&mut self, page: usize, other: usize) {
+        let (Some(with_page), Some(with_other)) = (
+            self.chapters_by_page.get(&page),
+            self.chapters_by_page.get(&other),
+        ) else {
+            return;
+        };
+        let affected: Vec<usize> = with_page.intersection(with_other).cloned().collect();
+
+        for index in affected {
+            let status = self.evaluate(&self.manual.chapters[index]);
+            self.count(&status, true);
+            let old = std::mem::replace(&mut self.statuses[index], status);
+            self.count(&old, false);
+        }
+    }
+
+    fn evaluate(&self, chapter: &[usize]) -> ChapterStatus {
+        if self.manual.is_ordered(chapter) {
+            return ChapterStatus::Ordered {
+                middle: self.manual.middle_page(chapter),
+            };
+        }
+        match self.manual.fix_chapter(chapter) {
+            Ok(fixed) => ChapterStatus::Fixed {
+                middle: self.manual.middle_page(&fixed),
+            },
+            Err(e) => ChapterStatus::Unfixable(e),
+        }
+    }
+
+    // Adds the status to the sums, or takes it out of them.
+    fn count(&mut self, status: &ChapterStatus, add: bool) {
+        let (sum, value) = match status {
+            ChapterStatus::Ordered { middle } => (&mut self.ordered_sum, *middle),
+            ChapterStatus::Fixed { middle } => (&mut self.fixed_sum, *middle),
+            ChapterStatus::Unfixable(_) => (&mut self.unfixable, 1),
+        };
+        match add {
+            true => *sum += value,
+            false => *sum -= value,
+        }
+    }
+}
@@ -1,4 +1,39 @@
-use std::collections::HashMap;
+use crate::incremental::{ChapterStatus, Command, IncrementalManual};
+use crate::rule_graph::RuleGraph;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::io::BufRead;
+
+mod incremental;
+mod orderings;
+mod rule_graph;
+mod violations;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnfixableChapter {
+    pub chapter: Vec<usize>,
+    // Every page must precede the next one, and the last one must precede the first.
+    pub cycle: Vec<usize>,
+}
+
+#[derive(Clone, Copy)]
+enum Mark {
+    Pending,
+    Visiting,
+    Placed,
+}
+
+impl fmt::Display for UnfixableChapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "chapter {:?} cannot be fixed, the rules between pages {:?} form a cycle",
+            self.chapter, self.cycle
+        )
+    }
+}
+
+impl std::error::Error for UnfixableChapter {}
 
 struct SafetyManual {
     rules: HashMap<usize, Vec<usize>>,
@@ -16,12 +51,7 @@ impl SafetyManual {
                 let key = parts.next().unwrap().parse::<usize>().unwrap();
                 let value = parts.next().unwrap().parse::<usize>().unwrap();
 
-                if rules.contains_key(&key) {
-                    let vec: &mut Vec<usize> = rules.get_mut(&key).unwrap();
-                    vec.push(value);
-                } else {
-                    rules.insert(key, vec![value]);
-                }
+                rules.entry(key).or_insert_with(Vec::new).push(value);
             }
             if line.contains(',') {
                 let chapter = line
@@ -37,6 +67,24 @@ impl SafetyManual {
         SafetyManual { rules, chapters }
     }
 
+    // Every page that appears in a rule or in a chapter.
+    pub fn rule_graph(&self) -> RuleGraph {
+        let pages: BTreeSet<usize> = self
+            .rules
+            .iter()
+            .flat_map(|(page, afterwards)| afterwards.iter().chain([page]))
+            .chain(self.chapters.iter().flatten())
+            .cloned()
+            .collect();
+        RuleGraph::new(&self.rules, &pages.into_iter().collect::<Vec<usize>>())
+    }
+
+    pub fn chapter_rule_graph(&self, chapter: usize) -> Option<RuleGraph> {
+        self.chapters
+            .get(chapter)
+            .map(|pages| RuleGraph::new(&self.rules, pages))
+    }
+
     pub fn count_ordered_pages(&self) -> usize {
         self.chapters
             .iter()
@@ -45,11 +93,11 @@ impl SafetyManual {
             .sum()
     }
 
-    pub fn count_fixes(&self) -> usize {
+    pub fn count_fixes(&self) -> Result<usize, UnfixableChapter> {
         self.chapters
             .iter()
             .filter(|c| !self.is_ordered(c))
-            .map(|c| self.fix_page(c))
+            .map(|c| self.fix_chapter(c).map(|fixed| self.middle_page(&fixed)))
             .sum()
     }
 
@@ -80,30 +128,61 @@ impl SafetyManual {
             .map(|(index, _)| index)
     }
 
-    fn fix_page(&self, chapter: &[usize]) -> usize {
-        let mut current = chapter.to_vec();
-        println!("{:?}", &current);
-        while !self.is_ordered(&current) {
-            let (first_bad_index, n) = current
-                .iter()
-                .enumerate()
-                .find(|(index, page)| !self.item_fulfills_restrictions(**page, &current[*index..]))
-                .unwrap();
+    // Topological sort of the chapter using only the rules between its pages:
+    // before placing a page, every page of the chapter that must precede it is
+    // placed, so pages that are already fine keep their relative order.
+    fn fix_chapter(&self, chapter: &[usize]) -> Result<Vec<usize>, UnfixableChapter> {
+        let mut marks = vec![Mark::Pending; chapter.len()];
+        let mut path = vec![];
+        let mut fixed = Vec::with_capacity(chapter.len());
 
-            let position = self.first_restriction_not_fulfilled(*n, &current[first_bad_index..]).unwrap();
+        for index in 0..chapter.len() {
+            self.place(chapter, index, &mut marks, &mut path, &mut fixed)
+                .map_err(|cycle| UnfixableChapter {
+                    chapter: chapter.to_vec(),
+                    cycle,
+                })?;
+        }
 
-            let position_to_move = first_bad_index + position;
+        Ok(fixed)
+    }
 
-            let mut new_vec = vec![];
+    fn place(
+        &self,
+        chapter: &[usize],
+        index: usize,
+        marks: &mut [Mark],
+        path: &mut Vec<usize>,
+        fixed: &mut Vec<usize>,
+    ) -> Result<(), Vec<usize>> {
+        match marks[index] {
+            Mark::Placed => return Ok(()),
+            Mark::Visiting => {
+                let start = path.iter().position(|i| *i == index).unwrap();
+                return Err(path[start..].iter().rev().map(|i| chapter[*i]).collect());
+            }
+            Mark::Pending => {}
+        }
 
-            new_vec.extend_from_slice(&current[..first_bad_index]);
-            new_vec.push(current[position_to_move]);
-            new_vec.extend_from_slice(&current[first_bad_index..position_to_move]);
-            new_vec.extend_from_slice(&current[position_to_move + 1..]);
-            current = new_vec;
+        marks[index] = Mark::Visiting;
+        path.push(index);
+        for other in 0..chapter.len() {
+            if other != index && self.must_precede(chapter[other], chapter[index]) {
+                self.place(chapter, other, marks, path, fixed)?;
+            }
         }
-        println!("{:?}", &current);
-        self.middle_page(&current)
+        path.pop();
+        marks[index] = Mark::Placed;
+        fixed.push(chapter[index]);
+
+        Ok(())
+    }
+
+    fn must_precede(&self, page: usize, other: usize) -> bool {
+        self.rules
+            .get(&page)
+            .map(|restrictions| restrictions.contains(&other))
+            .unwrap_or(false)
     }
 }
 
@@ -112,22 +191,130 @@ fn step1(input: &str) -> usize {
     manual.count_ordered_pages()
 }
 
-fn step2(input: &str) -> usize {
+fn step2(input: &str) -> Result<usize, UnfixableChapter> {
     let manual = SafetyManual::from_str(input);
     manual.count_fixes()
 }
 
+const ORDERINGS_SHOWN: usize = 20;
+
+fn print_answers(manual: &IncrementalManual) {
+    let chapters = manual.manual().chapters.len();
+    let unfixable = (0..chapters)
+        .filter(|i| matches!(manual.status(*i), ChapterStatus::Unfixable(_)))
+        .count();
+    println!("{} chapters, {} unfixable", chapters, unfixable);
+    println!("step 1: {}", manual.ordered_sum());
+    match manual.fixed_sum() {
+        Ok(res2) => println!("step 2: {}", res2),
+        Err(e) => println!("step 2: {}", e),
+    }
+}
+
+fn unknown_chapter(manual: &SafetyManual) -> ! {
+    eprintln!("expected a chapter index below {}", manual.chapters.len());
+    std::process::exit(1)
+}
+
 fn main() {
     let input = include_str!("../input.txt");
+    let args: Vec<String> = std::env::args().collect();
+
+    if let Some(index) = args.iter().position(|arg| arg == "--dot") {
+        let manual = SafetyManual::from_str(input);
+        let graph = match args.get(index + 1) {
+            Some(chapter) => chapter
+                .parse()
+                .ok()
+                .and_then(|chapter| manual.chapter_rule_graph(chapter))
+                .unwrap_or_else(|| unknown_chapter(&manual)),
+            None => manual.rule_graph(),
+        };
+        print!("{}", graph.to_dot());
+        return;
+    }
+
+    if args.iter().any(|arg| arg == "--ambiguous") {
+        let manual = SafetyManual::from_str(input);
+        let ambiguous = manual.ambiguous_chapters();
+        for (index, pages) in &ambiguous {
+            let chapter = &manual.chapters[*index];
+            let orderings = manual.count_orderings(chapter);
+            let count = |count: Option<u128>| count.map_or("unknown".to_string(), |c| c.to_string());
+            println!("chapter {}: {:?}, {} orderings", index, chapter, count(orderings));
+            for (page, orderings) in pages {
+                println!("  middle {} in {} orderings", page, count(*orderings));
+            }
+        }
+        println!("{} ambiguous chapters", ambiguous.len());
+        return;
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--orderings") {
+        let manual = SafetyManual::from_str(input);
+        let chapter = args
+            .get(index + 1)
+            .and_then(|chapter| chapter.parse::<usize>().ok())
+            .and_then(|chapter| manual.chapters.get(chapter))
+            .unwrap_or_else(|| unknown_chapter(&manual));
+        match manual.count_orderings(chapter) {
+            Some(count) => println!("{} orderings", count),
+            None => println!("too many pages to count the orderings"),
+        }
+        for ordering in manual.orderings(chapter).take(ORDERINGS_SHOWN) {
+            println!("{:?}", ordering);
+        }
+        return;
+    }
+
+    // Reads commands from stdin: `a|b` / `-a|b` add and remove rules,
+    // `1,2,3` adds a chapter and `-index` removes one.
+    if args.iter().any(|arg| arg == "--interactive") {
+        let mut manual = IncrementalManual::new(SafetyManual::from_str(input));
+        print_answers(&manual);
+        for line in std::io::stdin().lock().lines() {
+            match Command::from_str(&line.unwrap()) {
+                Some(command) if manual.apply(command.clone()) => print_answers(&manual),
+                Some(command) => println!("nothing changed by {:?}", command),
+                None => println!("unknown command"),
+            }
+        }
+        return;
+    }
+
+    if args.iter().any(|arg| arg == "--report") {
+        print!("{}", SafetyManual::from_str(input).report());
+        return;
+    }
+
+    if args.iter().any(|arg| arg == "--analyze") {
+        let analysis = SafetyManual::from_str(input).rule_graph().analyze();
+        println!("cycles: {:?}", analysis.cycles);
+        println!("rules inside cycles: {}", analysis.cycle_rules.len());
+        println!("redundant rules: {}", analysis.redundant_rules.len());
+        for (page, other) in analysis.redundant_rules {
+            println!("  {}|{}", page, other);
+        }
+        println!("unconstrained pages: {:?}", analysis.unconstrained_pages);
+        return;
+    }
+
     let res1 = step1(input);
-    let res2 = step2(input);
     println!("step 1: {}", res1);
-    println!("step 2: {}", res2);
+    match step2(input) {
+        Ok(res2) => println!("step 2: {}", res2),
+        Err(e) => println!("step 2: {}", e),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::orderings::MAX_DP_PAGES;
+    use crate::rule_graph::Analysis;
+    use crate::violations::Violation;
+    use std::collections::BTreeMap;
+    use std::fmt::Write;
 
     #[test]
     fn test_single_number_with_one_rule_returns_that_number() {
@@ -155,6 +342,403 @@ mod tests {
             "1,10,12\n",
             "11,20,23\n",
         );
-        assert_eq!(step2(input), 1);
+        assert_eq!(step2(input), Ok(1));
+    }
+
+    #[test]
+    fn step2_example_from_text() {
+        let input = concat!(
+            "47|53\n", "97|13\n", "97|61\n", "97|47\n", "75|29\n", "61|13\n", "75|53\n",
+            "29|13\n", "97|29\n", "53|29\n", "61|53\n", "97|53\n", "61|29\n", "47|13\n",
+            "75|47\n", "97|75\n", "47|61\n", "75|61\n", "47|29\n", "75|13\n", "53|13\n",
+            "\n",
+            "75,47,61,53,29\n",
+            "97,61,53,29,13\n",
+            "75,29,13\n",
+            "75,97,47,61,53\n",
+            "61,13,29\n",
+            "97,13,75,29,47\n",
+        );
+        assert_eq!(step1(input), 143);
+        assert_eq!(step2(input), Ok(123));
+    }
+
+    #[test]
+    fn fixing_keeps_unconstrained_pages_in_place() {
+        let manual = SafetyManual::from_str(concat!("3|1\n", "\n", "1,5,3,7\n"));
+
+        assert_eq!(manual.fix_chapter(&[1, 5, 3, 7]), Ok(vec![3, 1, 5, 7]));
+    }
+
+    #[test]
+    fn rule_cycle_makes_a_chapter_unfixable() {
+        let input = concat!("1|2\n", "2|3\n", "3|1\n", "\n", "3,2,1,4\n");
+        let error = step2(input).unwrap_err();
+
+        assert_eq!(
+            error,
+            UnfixableChapter {
+                chapter: vec![3, 2, 1, 4],
+                cycle: vec![1, 2, 3],
+            }
+        );
+    }
+
+    //
+    // Rule graph
+    //
+
+    #[test]
+    fn analysis_finds_cycles_redundant_rules_and_free_pages() {
+        let input = concat!(
+            "1|2\n", "2|3\n", "1|3\n", "4|5\n", "5|6\n", "6|4\n", "\n", "1,2,3\n", "7,4\n",
+        );
+        let analysis = SafetyManual::from_str(input).rule_graph().analyze();
+
+        assert_eq!(
+            analysis,
+            Analysis {
+                cycles: vec![vec![4, 5, 6]],
+                cycle_rules: vec![(4, 5), (5, 6), (6, 4)],
+                redundant_rules: vec![(1, 3)],
+                unconstrained_pages: vec![7],
+            }
+        );
+    }
+
+    #[test]
+    fn rules_inside_a_cycle_are_not_redundant() {
+        let input = concat!("1|2\n", "2|3\n", "3|1\n", "2|1\n", "1|4\n", "3|4\n", "\n");
+        let graph = SafetyManual::from_str(input).rule_graph();
+
+        assert_eq!(graph.strongly_connected_components(), vec![vec![1, 2, 3], vec![4]]);
+        assert_eq!(graph.cycle_rules(), vec![(1, 2), (2, 1), (2, 3), (3, 1)]);
+        assert_eq!(graph.redundant_rules(), vec![(3, 4)]);
+    }
+
+    #[test]
+    fn chapter_graph_only_has_the_rules_between_its_pages() {
+        let input = concat!("1|2\n", "2|3\n", "3|4\n", "\n", "1,2,4\n");
+        let manual = SafetyManual::from_str(input);
+
+        let graph = manual.chapter_rule_graph(0).unwrap();
+        assert_eq!(graph.rules().collect::<Vec<(usize, usize)>>(), vec![(1, 2)]);
+        assert_eq!(graph.unconstrained_pages(), vec![4]);
+        assert!(manual.chapter_rule_graph(1).is_none());
+    }
+
+    #[test]
+    fn dot_export() {
+        let input = concat!("1|2\n", "2|3\n", "1|3\n", "\n", "1,2,3,9\n");
+        let dot = SafetyManual::from_str(input).rule_graph().to_dot();
+
+        assert_eq!(
+            dot,
+            concat!(
+                "digraph rules {\n",
+                "    9;\n",
+                "    1 -> 2;\n",
+                "    1 -> 3 [style=dashed];\n",
+                "    2 -> 3;\n",
+                "}\n",
+            )
+        );
+    }
+
+    //
+    // Violations
+    //
+
+    fn example() -> SafetyManual {
+        SafetyManual::from_str(concat!(
+            "47|53\n", "97|13\n", "97|61\n", "97|47\n", "75|29\n", "61|13\n", "75|53\n",
+            "29|13\n", "97|29\n", "53|29\n", "61|53\n", "97|53\n", "61|29\n", "47|13\n",
+            "75|47\n", "97|75\n", "47|61\n", "75|61\n", "47|29\n", "75|13\n", "53|13\n",
+            "\n",
+            "75,47,61,53,29\n",
+            "97,61,53,29,13\n",
+            "75,29,13\n",
+            "75,97,47,61,53\n",
+            "61,13,29\n",
+            "97,13,75,29,47\n",
+        ))
+    }
+
+    #[test]
+    fn violations_list_every_broken_rule_with_positions() {
+        let manual = example();
+
+        assert_eq!(
+            manual.violations(&[61, 13, 29]),
+            vec![Violation {
+                page: 29,
+                other: 13,
+                page_position: 2,
+                other_position: 1
+            }]
+        );
+        assert_eq!(manual.violations(&[97, 13, 75, 29, 47]).len(), 4);
+        assert!(manual.violations(&[75, 47, 61, 53, 29]).is_empty());
+    }
+
+    #[test]
+    fn minimum_moves_to_fix_a_chapter() {
+        let manual = example();
+
+        assert_eq!(manual.minimum_moves(&[75, 47, 61, 53, 29]), Ok(0));
+        assert_eq!(manual.minimum_moves(&[75, 97, 47, 61, 53]), Ok(1));
+        assert_eq!(manual.minimum_moves(&[97, 13, 75, 29, 47]), Ok(2));
+        assert_eq!(manual.minimum_moves(&[53, 61, 47, 75, 97]), Ok(4));
+    }
+
+    #[test]
+    fn minimum_moves_when_rules_only_partially_order_the_chapter() {
+        let manual = SafetyManual::from_str(concat!("3|1\n", "5|4\n", "\n"));
+
+        assert_eq!(manual.minimum_moves(&[1, 4, 3, 5]), Ok(2));
+        assert_eq!(manual.minimum_moves(&[1, 3, 4]), Ok(1));
+    }
+
+    #[test]
+    fn minimum_moves_of_many_independent_swaps() {
+        let mut input = String::new();
+        for i in 0..22 {
+            writeln!(input, "{}|{}", 2 * i + 1, 2 * i).unwrap();
+        }
+        let manual = SafetyManual::from_str(&(input + "\n"));
+        let chapter: Vec<usize> = (0..44).collect();
+
+        assert_eq!(manual.minimum_moves(&chapter), Ok(22));
+    }
+
+    #[test]
+    fn report_annotates_every_chapter() {
+        let manual = SafetyManual::from_str(concat!(
+            "47|53\n", "53|29\n", "\n", "47,53,29\n", "29,47,53\n"
+        ));
+
+        assert_eq!(
+            manual.report(),
+            concat!(
+                "47,53,29: ordered, middle 53\n",
+                "29,47,53: 1 violated rules, 1 moves, middle 47 -> 53\n",
+                "  53|29: 29 at 0, 53 at 2\n",
+            )
+        );
+    }
+
+    #[test]
+    fn report_of_an_unfixable_chapter() {
+        let manual = SafetyManual::from_str(concat!("1|2\n", "2|1\n", "\n", "1,2\n"));
+
+        assert_eq!(
+            manual.report(),
+            concat!(
+                "1,2: 1 violated rules, chapter [1, 2] cannot be fixed, ",
+                "the rules between pages [2, 1] form a cycle\n",
+                "  2|1: 1 at 0, 2 at 1\n",
+            )
+        );
+    }
+
+    //
+    // Orderings
+    //
+
+    #[test]
+    fn a_totally_ordered_chapter_has_a_single_ordering() {
+        let manual = example();
+
+        assert_eq!(manual.count_orderings(&[97, 13, 75, 29, 47]), Some(1));
+        assert_eq!(
+            manual.orderings(&[97, 13, 75, 29, 47]).collect::<Vec<_>>(),
+            vec![vec![97, 75, 47, 29, 13]]
+        );
+        assert_eq!(
+            manual.middle_pages(&[97, 13, 75, 29, 47]),
+            Some(BTreeMap::from([(47, 1)]))
+        );
+    }
+
+    #[test]
+    fn orderings_of_a_partially_ordered_chapter() {
+        let manual = SafetyManual::from_str(concat!("1|2\n", "1|3\n", "\n"));
+
+        assert_eq!(manual.count_orderings(&[3, 2, 1]), Some(2));
+        assert_eq!(
+            manual.orderings(&[3, 2, 1]).collect::<Vec<_>>(),
+            vec![vec![1, 3, 2], vec![1, 2, 3]]
+        );
+        assert_eq!(
+            manual.middle_pages(&[3, 2, 1]),
+            Some(BTreeMap::from([(2, 1), (3, 1)]))
+        );
+    }
+
+    #[test]
+    fn unconstrained_chapter_has_every_permutation() {
+        let manual = SafetyManual::from_str("");
+        let chapter: Vec<usize> = (1..=6).collect();
+
+        assert_eq!(manual.count_orderings(&chapter), Some(720));
+        assert_eq!(manual.orderings(&chapter).count(), 720);
+        assert_eq!(
+            manual.middle_pages(&chapter),
+            Some((1..=6).map(|page| (page, 120)).collect())
+        );
+    }
+
+    #[test]
+    fn orderings_count_matches_enumeration() {
+        let manual = SafetyManual::from_str(concat!(
+            "1|4\n", "2|4\n", "4|6\n", "3|5\n", "5|7\n", "2|7\n", "\n"
+        ));
+        let chapter = [7, 6, 5, 4, 3, 2, 1];
+        let orderings: Vec<Vec<usize>> = manual.orderings(&chapter).collect();
+
+        assert_eq!(manual.count_orderings(&chapter), Some(orderings.len() as u128));
+        assert!(orderings.iter().all(|o| manual.is_ordered(o)));
+
+        let mut middles = BTreeMap::new();
+        for ordering in &orderings {
+            *middles.entry(manual.middle_page(ordering)).or_insert(0) += 1;
+        }
+        assert_eq!(manual.middle_pages(&chapter), Some(middles));
+    }
+
+    #[test]
+    fn a_chapter_with_a_cycle_has_no_orderings() {
+        let manual = SafetyManual::from_str(concat!("1|2\n", "2|3\n", "3|1\n", "\n"));
+
+        assert_eq!(manual.count_orderings(&[1, 2, 3, 4]), Some(0));
+        assert_eq!(manual.orderings(&[1, 2, 3, 4]).count(), 0);
+        assert_eq!(manual.middle_pages(&[1, 2, 3, 4]), Some(BTreeMap::new()));
+    }
+
+    #[test]
+    fn long_unconstrained_chapters_are_not_counted() {
+        let manual = SafetyManual::from_str("");
+        let chapter: Vec<usize> = (0..=MAX_DP_PAGES).collect();
+
+        assert_eq!(manual.count_orderings(&chapter), None);
+        assert_eq!(manual.middle_pages(&chapter), None);
+    }
+
+    #[test]
+    fn ambiguous_chapters_are_reported() {
+        let manual = SafetyManual::from_str(concat!("1|2\n", "1|3\n", "\n", "1,2,3\n", "1,2\n"));
+
+        assert_eq!(
+            manual.ambiguous_chapters(),
+            vec![(0, BTreeMap::from([(2, Some(1)), (3, Some(1))]))]
+        );
+        assert!(example().ambiguous_chapters().is_empty());
+    }
+
+    #[test]
+    fn long_chapters_are_ambiguous_with_unknown_counts() {
+        let chapter = (1..=22).map(|page| page.to_string()).collect::<Vec<String>>().join(",");
+        let manual = SafetyManual::from_str(&format!("1|2\n\n{}\n", chapter));
+
+        let ambiguous = manual.ambiguous_chapters();
+        assert_eq!(ambiguous.len(), 1);
+        assert_eq!(ambiguous[0].1.len(), 22);
+        assert!(ambiguous[0].1.values().all(|count| count.is_none()));
+
+        // 1 must be first, so it can't be in the middle.
+        let mut input = String::new();
+        for page in 2..=22 {
+            writeln!(input, "1|{}", page).unwrap();
+        }
+        let manual = SafetyManual::from_str(&format!("{}\n{}\n", input, chapter));
+        let ambiguous = manual.ambiguous_chapters();
+        let middles: Vec<usize> = ambiguous[0].1.keys().cloned().collect();
+        assert_eq!(middles, (2..=22).collect::<Vec<usize>>());
+    }
+
+    //
+    // Incremental updates
+    //
+
+    #[test]
+    fn incremental_manual_starts_with_the_answers() {
+        let manual = IncrementalManual::new(example());
+
+        assert_eq!(manual.ordered_sum(), 143);
+        assert_eq!(manual.fixed_sum(), Ok(123));
+        assert_eq!(manual.status(0), &ChapterStatus::Ordered { middle: 61 });
+        assert_eq!(manual.status(3), &ChapterStatus::Fixed { middle: 47 });
+    }
+
+    #[test]
+    fn removing_and_adding_back_a_rule() {
+        let mut manual = IncrementalManual::new(example());
+
+        // 75,97,47,61,53 is only wrong because of 97|75.
+        assert!(manual.remove_rule(97, 75));
+        assert_eq!(manual.status(3), &ChapterStatus::Ordered { middle: 47 });
+        assert_eq!(manual.ordered_sum(), 143 + 47);
+        assert_eq!(manual.fixed_sum(), Ok(123 - 47));
+        assert!(!manual.remove_rule(97, 75));
+
+        assert!(manual.add_rule(97, 75));
+        assert!(!manual.add_rule(97, 75));
+        assert_eq!(manual.ordered_sum(), 143);
+        assert_eq!(manual.fixed_sum(), Ok(123));
+    }
+
+    #[test]
+    fn adding_and_removing_chapters() {
+        let mut manual = IncrementalManual::new(example());
+
+        assert_eq!(manual.add_chapter(vec![53, 47, 97]), 6);
+        assert_eq!(manual.status(6), &ChapterStatus::Fixed { middle: 47 });
+        assert_eq!(manual.fixed_sum(), Ok(123 + 47));
+
+        assert_eq!(manual.remove_chapter(0), Some(vec![75, 47, 61, 53, 29]));
+        assert_eq!(manual.ordered_sum(), 143 - 61);
+        assert_eq!(manual.remove_chapter(6), None);
+
+        // Indexes moved, the rule must still reach the chapter now at 5.
+        assert!(manual.remove_rule(47, 53));
+        assert_eq!(manual.status(5), &ChapterStatus::Fixed { middle: 53 });
+        assert!(manual.remove_rule(97, 53));
+        assert_eq!(manual.status(5), &ChapterStatus::Fixed { middle: 97 });
+    }
+
+    #[test]
+    fn incremental_answers_match_a_full_recomputation() {
+        let commands = [
+            "-97|75", "+13|97", "-61|13", "13,61,97", "-1", "53|97", "-13|97", "-53|97", "+97|75",
+        ];
+        let mut manual = IncrementalManual::new(example());
+
+        for command in commands {
+            manual.apply(Command::from_str(command).unwrap());
+            let full = manual.manual();
+            assert_eq!(manual.ordered_sum(), full.count_ordered_pages());
+            assert_eq!(manual.fixed_sum(), full.count_fixes());
+        }
+    }
+
+    #[test]
+    fn a_rule_closing_a_cycle_makes_step2_fail_until_removed() {
+        let mut manual = IncrementalManual::new(example());
+
+        manual.add_rule(13, 97);
+        assert!(matches!(manual.status(1), ChapterStatus::Unfixable(_)));
+        assert!(manual.fixed_sum().is_err());
+        manual.remove_rule(13, 97);
+        assert_eq!(manual.fixed_sum(), Ok(123));
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(Command::from_str("47|53"), Some(Command::AddRule(47, 53)));
+        assert_eq!(Command::from_str("+47|53"), Some(Command::AddRule(47, 53)));
+        assert_eq!(Command::from_str("-47|53"), Some(Command::RemoveRule(47, 53)));
+        assert_eq!(Command::from_str("1,2,3"), Some(Command::AddChapter(vec![1, 2, 3])));
+        assert_eq!(Command::from_str("-3"), Some(Command::RemoveChapter(3)));
+        assert_eq!(Command::from_str("a|b"), None);
     }
 }
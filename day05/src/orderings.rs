@@ -0,0 +1,246 @@
+use crate::SafetyManual;
+use std::collections::BTreeMap;
+
+// The bitmask DP keeps a counter for every subset of the chapter.
+pub const MAX_DP_PAGES: usize = 20;
+
+// Every valid ordering of a chapter, built by placing at each step one of the
+// pages whose predecessors are already placed. Candidates are tried in the
+// order they appear in the chapter.
+pub struct Orderings {
+    chapter: Vec<usize>,
+    // predecessors[i]: positions of the pages that must precede chapter[i].
+    predecessors: Vec<Vec<usize>>,
+    placed: Vec<usize>,
+    used: Vec<bool>,
+    // next_candidate[depth]: first position to try when placing a page at `depth`.
+    next_candidate: Vec<usize>,
+    done: bool,
+}
+
+impl Iterator for Orderings {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Vec<usize>> {
+        while !self.done {
+            let depth = self.placed.len();
+            if depth == self.chapter.len() {
+                let res = self.placed.iter().map(|i| self.chapter[*i]).collect();
+                self.backtrack();
+                return Some(res);
+            }
+
+            let start = self.next_candidate[depth];
+            let candidate = (start..self.chapter.len())
+                .find(|i| !self.used[*i] && self.predecessors[*i].iter().all(|p| self.used[*p]));
+            match candidate {
+                Some(index) => {
+                    self.next_candidate[depth] = index + 1;
+                    self.used[index] = true;
+                    self.placed.push(index);
+                    self.next_candidate.push(0);
+                }
+                None => self.backtrack(),
+            }
+        }
+        None
+    }
+}
+
+impl Orderings {
+    fn backtrack(&mut self) {
+        self.next_candidate.pop();
+        match self.placed.pop() {
+            Some(index) => self.used[index] = false,
+            None => self.done = true,
+        }
+    }
+}
+
+impl SafetyManual {
+    // Number of orderings of the chapter that follow every rule between its
+    // pages. `None` when the chapter is not totally ordered by the rules and
+    // too long for the DP.
+    pub fn count_orderings(&self, chapter: &[usize]) -> Option<u128> {
+        if let Some(unique) = self.unique_ordering(chapter) {
+            return Some(unique.map_or(0, |_| 1));
+        }
+        let forward = self.forward_counts(chapter)?;
+        Some(forward[forward.len() - 1])
+    }
+
+    pub fn orderings(&self, chapter: &[usize]) -> Orderings {
+        Orderings {
+            chapter: chapter.to_vec(),
+            predecessors: self.predecessors(chapter),
+            placed: vec![],
+            used: vec![false; chapter.len()],
+            next_candidate: vec![0],
+            done: false,
+        }
+    }
+
+    // For every page that can be the middle one, in how many orderings it is.
+    pub fn middle_pages(&self, chapter: &[usize]) -> Option<BTreeMap<usize, u128>> {
+        if let Some(unique) = self.unique_ordering(chapter) {
+            return Some(
+                unique
+                    .map(|u| (self.middle_page(&u), 1))
+                    .into_iter()
+                    .collect(),
+            );
+        }
+
+        let forward = self.forward_counts(chapter)?;
+        let backward = self.backward_counts(chapter);
+        let predecessors = self.predecessor_masks(chapter);
+        let middle = chapter.len() / 2;
+
+        let mut res = BTreeMap::new();
+        for mask in 0..forward.len() {
+            if mask.count_ones() as usize != middle || forward[mask] == 0 {
+                continue;
+            }
+            for (index, needed) in predecessors.iter().enumerate() {
+                let bit = 1 << index;
+                if mask & bit != 0 || mask & needed != *needed {
+                    continue;
+                }
+                let count = forward[mask] * backward[mask | bit];
+                if count > 0 {
+                    *res.entry(chapter[index]).or_insert(0) += count;
+                }
+            }
+        }
+        Some(res)
+    }
+
+    // Chapters, by index, whose middle page depends on the ordering chosen,
+    // with the orderings that put each page in the middle. Chapters too long
+    // for the DP only tell which pages can be in the middle.
+    pub fn ambiguous_chapters(&self) -> Vec<(usize, BTreeMap<usize, Option<u128>>)> {
+        self.chapters
+            .iter()
+            .enumerate()
+            .filter_map(|(index, chapter)| {
+                let pages: BTreeMap<usize, Option<u128>> = match self.middle_pages(chapter) {
+                    Some(pages) => pages.into_iter().map(|(page, n)| (page, Some(n))).collect(),
+                    None => self
+                        .possible_middle_pages(chapter)
+                        .into_iter()
+                        .map(|page| (page, None))
+                        .collect(),
+                };
+                Some((index, pages)).filter(|(_, pages)| pages.len() > 1)
+            })
+            .collect()
+    }
+
+    // A page can be at any position between the number of pages that must
+    // precede it and the number that must follow it, so this needs no DP.
+    fn possible_middle_pages(&self, chapter: &[usize]) -> Vec<usize> {
+        let reaches = self.reachability(chapter);
+        if (0..chapter.len()).any(|i| reaches[i][i]) {
+            return vec![];
+        }
+        let middle = chapter.len() / 2;
+
+        (0..chapter.len())
+            .filter(|i| {
+                let before = (0..chapter.len()).filter(|j| reaches[*j][*i]).count();
+                let after = (0..chapter.len()).filter(|j| reaches[*i][*j]).count();
+                before <= middle && middle < chapter.len() - after
+            })
+            .map(|i| chapter[i])
+            .collect()
+    }
+
+    // Kahn's algorithm, giving up as soon as two pages could go next. Puzzle
+    // inputs order every pair of pages of a chapter, so this avoids the DP.
+    // Some(None) means the rules have a cycle and there is no ordering.
+    fn unique_ordering(&self, chapter: &[usize]) -> Option<Option<Vec<usize>>> {
+        let predecessors = self.predecessors(chapter);
+        let mut pending: Vec<usize> = predecessors.iter().map(|p| p.len()).collect();
+        let mut used = vec![false; chapter.len()];
+        let mut res = Vec::with_capacity(chapter.len());
+
+        while res.len() < chapter.len() {
+            let mut ready = (0..chapter.len()).filter(|i| !used[*i] && pending[*i] == 0);
+            let Some(next) = ready.next() else {
+                return Some(None);
+            };
+            if ready.next().is_some() {
+                return None;
+            }
+
+            used[next] = true;
+            res.push(chapter[next]);
+            for (index, predecessors) in predecessors.iter().enumerate() {
+                if predecessors.contains(&next) {
+                    pending[index] -= 1;
+                }
+            }
+        }
+        Some(Some(res))
+    }
+
+    // forward[mask]: ways to order the pages in `mask` as the first ones.
+    fn forward_counts(&self, chapter: &[usize]) -> Option<Vec<u128>> {
+        if chapter.len() > MAX_DP_PAGES {
+            return None;
+        }
+        let predecessors = self.predecessor_masks(chapter);
+        let mut forward = vec![0u128; 1 << chapter.len()];
+        forward[0] = 1;
+
+        for mask in 0..forward.len() {
+            if forward[mask] == 0 {
+                continue;
+            }
+            for (index, needed) in predecessors.iter().enumerate() {
+                let bit = 1 << index;
+                if mask & bit == 0 && mask & needed == *needed {
+                    forward[mask | bit] += forward[mask];
+                }
+            }
+        }
+        Some(forward)
+    }
+
+    // backward[mask]: ways to order the pages not in `mask` after the ones in it.
+    fn backward_counts(&self, chapter: &[usize]) -> Vec<u128> {
+        let predecessors = self.predecessor_masks(chapter);
+        let mut backward = vec![0u128; 1 << chapter.len()];
+        let full = backward.len() - 1;
+        backward[full] = 1;
+
+        for mask in (0..full).rev() {
+            backward[mask] = predecessors
+                .iter()
+                .enumerate()
+                .filter(|(index, needed)| mask & (1 << index) == 0 && mask & **needed == **needed)
+                .map(|(index, _)| backward[mask | (1 << index)])
+                .sum();
+        }
+        backward
+    }
+
+    fn predecessors(&self, chapter: &[usize]) -> Vec<Vec<usize>> {
+        (0..chapter.len())
+            .map(|index| {
+                (0..chapter.len())
+                    .filter(|other| {
+                        *other != index && self.must_precede(chapter[*other], chapter[index])
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn predecessor_masks(&self, chapter: &[usize]) -> Vec<usize> {
+        self.predecessors(chapter)
+            .iter()
+            .map(|p| p.iter().fold(0, |mask, other| mask | (1 << other)))
+            .collect()
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+// The rules as a directed graph: an edge `a -> b` means page `a` must be
+// printed before page `b`.
+pub struct RuleGraph {
+    edges: BTreeMap<usize, BTreeSet<usize>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    pub cycles: Vec<Vec<usize>>,
+    pub cycle_rules: Vec<(usize, usize)>,
+    pub redundant_rules: Vec<(usize, usize)>,
+    pub unconstrained_pages: Vec<usize>,
+}
+
+impl RuleGraph {
+    // Only the given pages and the rules between them are part of the graph.
+    pub fn new(rules: &HashMap<usize, Vec<usize>>, pages: &[usize]) -> RuleGraph {
+        let mut edges: BTreeMap<usize, BTreeSet<usize>> =
+            pages.iter().map(|page| (*page, BTreeSet::new())).collect();
+
+        for (page, afterwards) in rules {
+            if !edges.contains_key(page) {
+                continue;
+            }
+            let reachable: Vec<usize> = afterwards
+                .iter()
+                .filter(|other| edges.contains_key(other))
+                .cloned()
+                .collect();
+            edges.get_mut(page).unwrap().extend(reachable);
+        }
+
+        RuleGraph { edges }
+    }
+
+    pub fn analyze(&self) -> Analysis {
+        Analysis {
+            cycles: self
+                .strongly_connected_components()
+                .into_iter()
+                .filter(|component| component.len() > 1 || self.has_rule(component[0], component[0]))
+                .collect(),
+            cycle_rules: self.cycle_rules(),
+            redundant_rules: self.redundant_rules(),
+            unconstrained_pages: self.unconstrained_pages(),
+        }
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.edges
+            .iter()
+            .flat_map(|(page, afterwards)| afterwards.iter().map(move |other| (*page, *other)))
+    }
+
+    fn has_rule(&self, page: usize, other: usize) -> bool {
+        self.edges.get(&page).map(|a| a.contains(&other)).unwrap_or(false)
+    }
+
+    // Tarjan's algorithm. Components and the pages inside them are sorted.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<usize>> {
+        let mut tarjan = Tarjan {
+            graph: self,
+            next_index: 0,
+            index: HashMap::new(),
+            low_link: HashMap::new(),
+            stack: vec![],
+            on_stack: BTreeSet::new(),
+            components: vec![],
+        };
+        for page in self.edges.keys() {
+            if !tarjan.index.contains_key(page) {
+                tarjan.visit(*page);
+            }
+        }
+
+        let mut components = tarjan.components;
+        components.iter_mut().for_each(|c| c.sort());
+        components.sort();
+        components
+    }
+
+    fn component_of(&self) -> HashMap<usize, usize> {
+        self.strongly_connected_components()
+            .into_iter()
+            .enumerate()
+            .flat_map(|(index, component)| component.into_iter().map(move |page| (page, index)))
+            .collect()
+    }
+
+    // Rules between pages of the same cycle. Dropping any of them can break
+    // the cycle, so they are never reported as redundant.
+    pub fn cycle_rules(&self) -> Vec<(usize, usize)> {
+        let component = self.component_of();
+        self.rules()
+            .filter(|(page, other)| component[page] == component[other])
+            .collect()
+    }
+
+    // A rule is redundant when it is implied by a chain of other rules. Only
+    // rules between different cycles (or single pages) are checked, against
+    // the graph where every cycle is collapsed into one node, so all the
+    // redundant rules can be dropped together. When several rules join the
+    // same two cycles, the first one is kept.
+    pub fn redundant_rules(&self) -> Vec<(usize, usize)> {
+        let component = self.component_of();
+        let mut condensed: BTreeMap<usize, BTreeSet<usize>> = component
+            .values()
+            .map(|index| (*index, BTreeSet::new()))
+            .collect();
+        for (page, other) in self.rules() {
+            if component[&page] != component[&other] {
+                condensed.get_mut(&component[&page]).unwrap().insert(component[&other]);
+            }
+        }
+
+        let mut kept = BTreeSet::new();
+        self.rules()
+            .filter(|(page, other)| {
+                let (from, to) = (component[page], component[other]);
+                from != to && (reaches_without(&condensed, from, to) || !kept.insert((from, to)))
+            })
+            .collect()
+    }
+
+    // Pages that appear in no rule with any other page.
+    pub fn unconstrained_pages(&self) -> Vec<usize> {
+        let constrained: BTreeSet<usize> = self
+            .rules()
+            .filter(|(page, other)| page != other)
+            .flat_map(|(page, other)| [page, other])
+            .collect();
+
+        self.edges
+            .keys()
+            .filter(|page| !constrained.contains(page))
+            .cloned()
+            .collect()
+    }
+
+    // Graphviz output. Redundant rules are dashed and rules inside a cycle red.
+    pub fn to_dot(&self) -> String {
+        let redundant: BTreeSet<(usize, usize)> = self.redundant_rules().into_iter().collect();
+        let cycle: BTreeSet<(usize, usize)> = self.cycle_rules().into_iter().collect();
+        let mut res = String::from("digraph rules {\n");
+
+        for page in self.unconstrained_pages() {
+            res.push_str(&format!("    {};\n", page));
+        }
+        for (page, other) in self.rules() {
+            if redundant.contains(&(page, other)) {
+                res.push_str(&format!("    {} -> {} [style=dashed];\n", page, other));
+            } else if cycle.contains(&(page, other)) {
+                res.push_str(&format!("    {} -> {} [color=red];\n", page, other));
+            } else {
+                res.push_str(&format!("    {} -> {};\n", page, other));
+            }
+        }
+
+        res.push_str("}\n");
+        res
+    }
+}
+
+// Tells if `to` is reachable from `from` without using the edge `from -> to`.
+fn reaches_without(edges: &BTreeMap<usize, BTreeSet<usize>>, from: usize, to: usize) -> bool {
+    let mut visited = BTreeSet::from([from]);
+    let mut queue = VecDeque::from([from]);
+
+    while let Some(node) = queue.pop_front() {
+        for next in &edges[&node] {
+            if node == from && *next == to {
+                continue;
+            }
+            if *next == to {
+                return true;
+            }
+            if visited.insert(*next) {
+                queue.push_back(*next);
+            }
+        }
+    }
+
+    false
+}
+
+struct Tarjan<'a> {
+    graph: &'a RuleGraph,
+    next_index: usize,
+    index: HashMap<usize, usize>,
+    low_link: HashMap<usize, usize>,
+    stack: Vec<usize>,
+    on_stack: BTreeSet<usize>,
+    components: Vec<Vec<usize>>,
+}
+
+impl Tarjan<'_> {
+    fn visit(&mut self, page: usize) {
+        self.index.insert(page, self.next_index);
+        self.low_link.insert(page, self.next_index);
+        self.next_index += 1;
+        self.stack.push(page);
+        self.on_stack.insert(page);
+
+        for next in &self.graph.edges[&page] {
+            if !self.index.contains_key(next) {
+                self.visit(*next);
+                let low = self.low_link[&page].min(self.low_link[next]);
+                self.low_link.insert(page, low);
+            } else if self.on_stack.contains(next) {
+                let low = self.low_link[&page].min(self.index[next]);
+                self.low_link.insert(page, low);
+            }
+        }
+
+        if self.low_link[&page] == self.index[&page] {
+            let mut component = vec![];
+            while let Some(other) = self.stack.pop() {
+                self.on_stack.remove(&other);
+                component.push(other);
+                if other == page {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use crate::SafetyManual;
+use std::fmt::Write;
+
+// Rule `page|other` broken because `other` is printed at `other_position`,
+// before `page` at `page_position`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub page: usize,
+    pub other: usize,
+    pub page_position: usize,
+    pub other_position: usize,
+}
+
+impl SafetyManual {
+    pub fn violations(&self, chapter: &[usize]) -> Vec<Violation> {
+        let mut res = vec![];
+        for (other_position, other) in chapter.iter().enumerate() {
+            for (page_position, page) in chapter.iter().enumerate().skip(other_position + 1) {
+                if self.must_precede(*page, *other) {
+                    res.push(Violation {
+                        page: *page,
+                        other: *other,
+                        page_position,
+                        other_position,
+                    });
+                }
+            }
+        }
+        res
+    }
+
+    // Every move takes one page and puts it somewhere else. The pages that are
+    // never moved keep their relative order, so no chain of rules can require
+    // a later one of them to precede an earlier one. The answer is the number
+    // of pages minus the largest group of pages that can stay.
+    #[cfg(test)]
+    pub fn minimum_moves(&self, chapter: &[usize]) -> Result<usize, crate::UnfixableChapter> {
+        self.fix_chapter(chapter)?;
+        Ok(self.moves(chapter))
+    }
+
+    // Pages in the wrong order (`j` after `i` but required before it) form a
+    // partial order: chains of rules are transitive. The pages that can stay
+    // are an antichain of it, and by Dilworth's theorem the largest antichain
+    // leaves out as many pages as the maximum matching between them.
+    fn moves(&self, chapter: &[usize]) -> usize {
+        let reaches = self.reachability(chapter);
+        let inverted: Vec<Vec<usize>> = (0..chapter.len())
+            .map(|i| ((i + 1)..chapter.len()).filter(|j| reaches[*j][i]).collect())
+            .collect();
+
+        let mut matched: Vec<Option<usize>> = vec![None; chapter.len()];
+        (0..chapter.len())
+            .filter(|i| augment(&inverted, *i, &mut vec![false; chapter.len()], &mut matched))
+            .count()
+    }
+
+    pub fn chapter_report(&self, chapter: &[usize]) -> String {
+        let listed = chapter
+            .iter()
+            .map(|page| page.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        let violations = self.violations(chapter);
+        if violations.is_empty() {
+            return format!(
+                "{}: ordered, middle {}\n",
+                listed,
+                self.middle_page(chapter)
+            );
+        }
+
+        let mut res = match self.fix_chapter(chapter) {
+            Ok(fixed) => format!(
+                "{}: {} violated rules, {} moves, middle {} -> {}\n",
+                listed,
+                violations.len(),
+                self.moves(chapter),
+                self.middle_page(chapter),
+                self.middle_page(&fixed)
+            ),
+            Err(e) => format!("{}: {} violated rules, {}\n", listed, violations.len(), e),
+        };
+        for v in violations {
+            writeln!(
+                res,
+                "  {}|{}: {} at {}, {} at {}",
+                v.page, v.other, v.other, v.other_position, v.page, v.page_position
+            )
+            .unwrap();
+        }
+        res
+    }
+
+    pub fn report(&self) -> String {
+        self.chapters
+            .iter()
+            .map(|c| self.chapter_report(c))
+            .collect()
+    }
+
+    // reaches[i][j] tells if a chain of rules between pages of the chapter
+    // requires the page at `i` to precede the page at `j`.
+    pub fn reachability(&self, chapter: &[usize]) -> Vec<Vec<bool>> {
+        let len = chapter.len();
+        let mut reaches: Vec<Vec<bool>> = (0..len)
+            .map(|i| {
+                (0..len)
+                    .map(|j| self.must_precede(chapter[i], chapter[j]))
+                    .collect()
+            })
+            .collect();
+
+        for k in 0..len {
+            for i in 0..len {
+                if reaches[i][k] {
+                    for j in 0..len {
+                        reaches[i][j] = reaches[i][j] || reaches[k][j];
+                    }
+                }
+            }
+        }
+        reaches
+    }
+}
+
+// Kuhn's augmenting path step: tries to match `left` with one of its `edges`,
+// moving already matched ones along if needed.
+fn augment(
+    edges: &[Vec<usize>],
+    left: usize,
+    visited: &mut [bool],
+    matched: &mut [Option<usize>],
+) -> bool {
+    for right in &edges[left] {
+        if visited[*right] {
+            continue;
+        }
+        visited[*right] = true;
+        if matched[*right].map_or(true, |other| augment(edges, other, visited, matched)) {
+            matched[*right] = Some(left);
+            return true;
+        }
+    }
+    false
+}
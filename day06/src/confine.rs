@@ -0,0 +1,104 @@
+use crate::jump_table::JumpTable;
+use crate::{OfficeWithAGuard, Point};
+use std::collections::HashSet;
+
+impl OfficeWithAGuard {
+    // Smallest set of extra obstacles, at most `limit` of them, that keeps the
+    // guard from leaving the map. Sets are tried by increasing size; only
+    // cells the guard walks through with the obstacles placed so far can
+    // change its patrol, so those are the only candidates for the next one.
+    pub fn confining_obstacles(&self, limit: usize) -> Option<Vec<Point>> {
+        for size in 0..=limit {
+            let mut obstacles = vec![];
+            if self.confine(&mut obstacles, size, &mut HashSet::new()) {
+                obstacles.sort();
+                return Some(obstacles);
+            }
+        }
+        None
+    }
+
+    // Adds obstacles one at a time, taking a confining one if there is any or
+    // else the one that makes the guard walk the longest before leaving. Much
+    // faster than `confining_obstacles`, but the set is not always the smallest.
+    pub fn confining_obstacles_greedy(&self, limit: usize) -> Option<Vec<Point>> {
+        let table = JumpTable::new(&self.map);
+        let start = table.cell(self.guard.position);
+        let mut seen = vec![0u8; table.cells()];
+        let mut obstacles: Vec<Point> = vec![];
+
+        loop {
+            let mut office = self.clone_with_obstacles(&obstacles);
+            if office.search_loop() {
+                obstacles.sort();
+                return Some(obstacles);
+            }
+            if obstacles.len() == limit {
+                return None;
+            }
+
+            let mut extras: Vec<usize> = obstacles.iter().map(|p| table.cell(*p)).collect();
+            let mut best: Option<(usize, Point)> = None;
+            for candidate in self.candidates(&office, &obstacles) {
+                extras.push(table.cell(candidate));
+                let walked = table
+                    .escape_distance(
+                        start,
+                        &self.guard.direction,
+                        &self.policy,
+                        &extras,
+                        &mut seen,
+                    )
+                    .unwrap_or(usize::MAX);
+                extras.pop();
+                if best.map_or(true, |(longest, _)| walked > longest) {
+                    best = Some((walked, candidate));
+                }
+            }
+
+            match best {
+                Some((_, candidate)) => obstacles.push(candidate),
+                None => return None,
+            }
+        }
+    }
+
+    fn confine(
+        &self,
+        obstacles: &mut Vec<Point>,
+        left: usize,
+        tried: &mut HashSet<Vec<Point>>,
+    ) -> bool {
+        let mut office = self.clone_with_obstacles(obstacles);
+        if office.search_loop() {
+            return true;
+        }
+        if left == 0 {
+            return false;
+        }
+
+        for candidate in self.candidates(&office, obstacles) {
+            obstacles.push(candidate);
+            let mut key = obstacles.clone();
+            key.sort();
+            if tried.insert(key) && self.confine(obstacles, left - 1, tried) {
+                return true;
+            }
+            obstacles.pop();
+        }
+        false
+    }
+
+    // Cells walked in `office`, sorted, except the start and the obstacles
+    // already placed.
+    fn candidates(&self, office: &OfficeWithAGuard, obstacles: &[Point]) -> Vec<Point> {
+        let mut candidates: Vec<Point> = office
+            .path
+            .keys()
+            .filter(|p| **p != self.guard.position && !obstacles.contains(p))
+            .cloned()
+            .collect();
+        candidates.sort();
+        candidates
+    }
+}
@@ -0,0 +1,100 @@
+use crate::steps::Step;
+use crate::{OfficeWithAGuard, Terrain};
+use std::fmt::Write;
+
+impl OfficeWithAGuard {
+    pub fn path_csv(&self) -> String {
+        let mut res = String::from("row,column,direction,turned\n");
+        for step in self.steps() {
+            let (row, column) = step.position;
+            writeln!(
+                res,
+                "{},{},{},{}",
+                row,
+                column,
+                step.direction.name(),
+                step.turned
+            )
+            .unwrap();
+        }
+        res
+    }
+
+    pub fn path_json(&self) -> String {
+        let steps: Vec<Step> = self.steps().collect();
+        let path: Vec<String> = steps
+            .iter()
+            .map(|step| {
+                format!(
+                    "{{\"row\":{},\"column\":{},\"direction\":\"{}\",\"turned\":{}}}",
+                    step.position.0,
+                    step.position.1,
+                    step.direction.name(),
+                    step.turned
+                )
+            })
+            .collect();
+        let turns: Vec<String> = steps
+            .iter()
+            .filter(|step| step.turned)
+            .map(|step| format!("[{},{}]", step.position.0, step.position.1))
+            .collect();
+
+        format!(
+            "{{\"path\":[{}],\"turns\":[{}]}}\n",
+            path.join(","),
+            turns.join(",")
+        )
+    }
+
+    // Like the drawings of the puzzle: `|` and `-` where the guard walked
+    // vertically or horizontally, `+` where it did both or turned, and the
+    // guard itself where it started.
+    pub fn path_map(&self) -> String {
+        let mut vertical = vec![vec![false; self.map[0].len()]; self.map.len()];
+        let mut horizontal = vertical.clone();
+        let mut turned = vertical.clone();
+        let mut previous: Option<Step> = None;
+
+        for step in self.steps() {
+            let (row, column) = (step.position.0 as usize, step.position.1 as usize);
+            // The direction the guard came in counts as well as the one it leaves in.
+            for direction in previous
+                .iter()
+                .map(|p| &p.direction)
+                .chain([&step.direction])
+            {
+                match direction.delta() {
+                    (0, _) => horizontal[row][column] = true,
+                    _ => vertical[row][column] = true,
+                }
+            }
+            turned[row][column] |= step.turned;
+            previous = Some(step);
+        }
+
+        let (start_row, start_column) = self.guard.position;
+        let mut res = String::new();
+        for (row, cells) in self.map.iter().enumerate() {
+            for (column, terrain) in cells.iter().enumerate() {
+                let glyph = match terrain {
+                    Terrain::Obstacle => '#',
+                    _ if (row as isize, column as isize) == (start_row, start_column) => {
+                        self.guard.direction.glyph()
+                    }
+                    _ if turned[row][column]
+                        || (vertical[row][column] && horizontal[row][column]) =>
+                    {
+                        '+'
+                    }
+                    _ if vertical[row][column] => '|',
+                    _ if horizontal[row][column] => '-',
+                    _ => '.',
+                };
+                res.push(glyph);
+            }
+            res.push('\n');
+        }
+        res
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{Direction, PatrolPolicy, Terrain};
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::North,
+    Direction::East,
+    Direction::South,
+    Direction::West,
+];
+
+// Where the guard stops when walking straight from a cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stop {
+    // Last free cell before an obstacle or the edge of the map.
+    pub cell: usize,
+    pub exits: bool,
+}
+
+// For every free cell and direction, the cell where the guard has to turn, so a
+// patrol is walked one straight segment at a time instead of cell by cell.
+// Cells are numbered row by row.
+pub struct JumpTable {
+    rows: usize,
+    columns: usize,
+    stops: Vec<Stop>,
+}
+
+impl JumpTable {
+    pub fn new(map: &[Vec<Terrain>]) -> JumpTable {
+        let rows = map.len();
+        let columns = map.first().map_or(0, |row| row.len());
+        let placeholder = Stop {
+            cell: 0,
+            exits: true,
+        };
+        let mut stops = vec![placeholder; rows * columns * DIRECTIONS.len()];
+
+        for direction in DIRECTIONS {
+            let (row_delta, column_delta) = direction.delta();
+            // Cells are visited so that the next one in `direction` is already done.
+            let row_order: Vec<usize> = match row_delta > 0 {
+                true => (0..rows).rev().collect(),
+                false => (0..rows).collect(),
+            };
+            let column_order: Vec<usize> = match column_delta > 0 {
+                true => (0..columns).rev().collect(),
+                false => (0..columns).collect(),
+            };
+
+            for row in &row_order {
+                for column in &column_order {
+                    let cell = row * columns + column;
+                    let next_row = *row as isize + row_delta;
+                    let next_column = *column as isize + column_delta;
+                    let stop = if next_row < 0
+                        || next_column < 0
+                        || next_row as usize >= rows
+                        || next_column as usize >= columns
+                    {
+                        Stop { cell, exits: true }
+                    } else {
+                        let next = next_row as usize * columns + next_column as usize;
+                        match map[next_row as usize][next_column as usize] {
+                            Terrain::Obstacle => Stop { cell, exits: false },
+                            Terrain::Free => stops[next * DIRECTIONS.len() + direction.index()],
+                        }
+                    };
+                    stops[cell * DIRECTIONS.len() + direction.index()] = stop;
+                }
+            }
+        }
+
+        JumpTable {
+            rows,
+            columns,
+            stops,
+        }
+    }
+
+    pub fn cells(&self) -> usize {
+        self.rows * self.columns
+    }
+
+    pub fn cell(&self, (row, column): (isize, isize)) -> usize {
+        row as usize * self.columns + column as usize
+    }
+
+    // Like `stop`, but as if there were also obstacles at `extras`.
+    pub fn stop_with(&self, cell: usize, direction: &Direction, extras: &[usize]) -> Stop {
+        let stop = self.stop(cell, direction);
+        let nearest = extras
+            .iter()
+            .filter(|extra| self.is_between(cell, stop.cell, direction, **extra))
+            .min_by_key(|extra| self.distance(cell, **extra));
+        match nearest {
+            Some(extra) => {
+                let (row_delta, column_delta) = direction.delta();
+                let before = *extra as isize - row_delta * self.columns as isize - column_delta;
+                Stop {
+                    cell: before as usize,
+                    exits: false,
+                }
+            }
+            None => stop,
+        }
+    }
+
+    pub fn stop(&self, cell: usize, direction: &Direction) -> Stop {
+        self.stops[cell * DIRECTIONS.len() + direction.index()]
+    }
+
+    // Whether the guard starting at `start` facing `direction` walks in
+    // circles forever once obstacles are added at `extras`. `seen` holds one bit
+    // per direction and policy phase for every cell and is cleared before use.
+    pub fn loops_with(
+        &self,
+        start: usize,
+        direction: &Direction,
+        policy: &PatrolPolicy,
+        extras: &[usize],
+        seen: &mut [u8],
+    ) -> bool {
+        self.escape_distance(start, direction, policy, extras, seen)
+            .is_none()
+    }
+
+    // Number of cells the guard walks before leaving the map, or `None` if it
+    // never does.
+    pub fn escape_distance(
+        &self,
+        start: usize,
+        direction: &Direction,
+        policy: &PatrolPolicy,
+        extras: &[usize],
+        seen: &mut [u8],
+    ) -> Option<usize> {
+        seen.fill(0);
+        let mut cell = start;
+        let mut direction = direction.clone();
+        let mut turns = 0;
+        let mut walked = 0;
+
+        loop {
+            let stop = self.stop_with(cell, &direction, extras);
+            walked += self.distance(cell, stop.cell);
+            if stop.exits {
+                return Some(walked);
+            }
+            let bit = 1 << (direction.index() + DIRECTIONS.len() * policy.phase(turns));
+            if seen[stop.cell] & bit != 0 {
+                return None;
+            }
+            seen[stop.cell] |= bit;
+            cell = stop.cell;
+            direction = policy.turn(&direction, turns);
+            turns += 1;
+        }
+    }
+
+    // Cells walked between two cells of the same row or column.
+    fn distance(&self, from: usize, to: usize) -> usize {
+        let (from_row, from_column) = (from / self.columns, from % self.columns);
+        let (to_row, to_column) = (to / self.columns, to % self.columns);
+        from_row.abs_diff(to_row) + from_column.abs_diff(to_column)
+    }
+
+    // Whether `cell` is one of the cells walked from `from` (excluded) to `to`.
+    fn is_between(&self, from: usize, to: usize, direction: &Direction, cell: usize) -> bool {
+        let (row, column) = (cell / self.columns, cell % self.columns);
+        let (from_row, from_column) = (from / self.columns, from % self.columns);
+        let (to_row, to_column) = (to / self.columns, to % self.columns);
+        match direction {
+            Direction::North => column == from_column && to_row <= row && row < from_row,
+            Direction::South => column == from_column && from_row < row && row <= to_row,
+            Direction::West => row == from_row && to_column <= column && column < from_column,
+            Direction::East => row == from_row && from_column < column && column <= to_column,
+        }
+    }
+}
@@ -1,6 +1,15 @@
-use std::collections::{HashMap};
-
-#[derive(Clone, PartialEq)]
+use crate::jump_table::JumpTable;
+use std::collections::{HashMap, HashSet};
+use std::thread;
+use std::time::Instant;
+
+mod confine;
+mod export;
+mod jump_table;
+mod parallel;
+mod steps;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Direction {
     North,
     West,
@@ -8,6 +17,19 @@ pub enum Direction {
     East,
 }
 
+// What the guard does in front of an obstacle. `Alternating` turns right the
+// first time, left the next one, and so on.
+#[derive(Clone, PartialEq, Debug)]
+pub enum PatrolPolicy {
+    TurnRight,
+    TurnLeft,
+    Reverse,
+    Alternating,
+}
+
+// Enough turns to face every direction the policy can reach.
+const MAX_TURNS: usize = 4;
+
 #[derive(Clone)]
 pub enum Terrain {
     Free,
@@ -20,18 +42,24 @@ type Point = (isize, isize);
 pub struct Guard {
     position: Point,
     direction: Direction,
+    turns: usize,
 }
 
-pub struct OfficeWithAGuard {
+// `guard` is the one walking; `guards` are all of them at their start, in
+// reading order, the first one being the `guard` of the puzzle.
+struct OfficeWithAGuard {
     guard: Guard,
+    guards: Vec<Guard>,
+    policy: PatrolPolicy,
     map: Vec<Vec<Terrain>>,
-    path: HashMap<Point, Vec<Direction>>
+    // Direction and policy phase of the guard every time it entered a cell.
+    path: HashMap<Point, Vec<(Direction, usize)>>,
 }
 
 impl OfficeWithAGuard {
     pub fn from_str(input: &str) -> OfficeWithAGuard {
         let mut map = vec![];
-        let mut guard: Option<Guard> = None;
+        let mut guards = vec![];
 
         input
             .lines()
@@ -45,45 +73,90 @@ impl OfficeWithAGuard {
                     .for_each(|(column_number, cell)| match cell {
                         '.' => row.push(Terrain::Free),
                         '#' => row.push(Terrain::Obstacle),
-                        '^' => {
+                        _ => {
+                            let direction = Direction::from_glyph(cell).expect("unknown cell");
                             row.push(Terrain::Free);
-                            guard.replace(Guard::new(
-                                Direction::North,
+                            guards.push(Guard::new(
+                                direction,
                                 (
                                     row_number.try_into().unwrap(),
                                     column_number.try_into().unwrap(),
                                 ),
                             ));
                         }
-                        _ => panic!("unknown cell"),
                     });
                 map.push(row);
             });
 
-        let guard = guard.unwrap();
+        let guard = guards.first().cloned().unwrap();
         let path = HashMap::new();
-        OfficeWithAGuard { map, guard, path }
+        OfficeWithAGuard {
+            map,
+            guard,
+            guards,
+            policy: PatrolPolicy::TurnRight,
+            path,
+        }
     }
 
-    pub fn patrol(&mut self) -> usize {
-        while self.guard_is_inside() {
-            self.register_step();
-            self.guard_step();
+    pub fn with_policy(mut self, policy: PatrolPolicy) -> OfficeWithAGuard {
+        self.policy = policy;
+        self
+    }
+
+    // Cells seen by each guard. Guards ignore each other, and a guard walking
+    // in circles sees the cells of its loop.
+    pub fn guard_coverages(&self) -> Vec<usize> {
+        self.guards
+            .iter()
+            .map(|guard| self.walk(guard).len())
+            .collect()
+    }
+
+    // Cells seen by at least one guard.
+    pub fn combined_coverage(&self) -> usize {
+        let mut seen = HashSet::new();
+        for guard in &self.guards {
+            seen.extend(self.walk(guard).into_keys());
         }
+        seen.len()
+    }
+
+    fn walk(&self, guard: &Guard) -> HashMap<Point, Vec<(Direction, usize)>> {
+        let mut office = OfficeWithAGuard {
+            path: HashMap::new(),
+            map: self.map.clone(),
+            guard: guard.clone(),
+            guards: vec![guard.clone()],
+            policy: self.policy.clone(),
+        };
+        office.search_loop();
+        office.path
+    }
+
+    // Stops when the guard leaves or, with some policies, starts walking in circles.
+    pub fn patrol(&mut self) -> usize {
+        self.search_loop();
         self.path.len()
     }
 
     fn register_step(&mut self) {
-        let value = self.path.get_mut(&self.guard.position);
-        if value.is_some() {
-            value.unwrap().push(self.guard.direction.clone());
-        } else {
-            self.path.insert(self.guard.position.clone(), vec![self.guard.direction.clone()]);
-        }
+        let state = self.guard_state();
+        self.path.entry(self.guard.position).or_default().push(state);
+    }
+
+    fn guard_state(&self) -> (Direction, usize) {
+        (
+            self.guard.direction.clone(),
+            self.policy.phase(self.guard.turns),
+        )
     }
 
     fn guard_is_inside(&self) -> bool {
-        let (row, column) = self.guard.position;
+        self.is_inside(self.guard.position)
+    }
+
+    fn is_inside(&self, (row, column): Point) -> bool {
         if row < 0 || column < 0 {
             return false;
         }
@@ -95,11 +168,16 @@ impl OfficeWithAGuard {
         option.is_some()
     }
 
-    fn guard_step(&mut self) {
-        while !self.guard_can_move() {
-            self.guard.rotate()
+    // False when the guard is boxed in and cannot move at all.
+    fn guard_step(&mut self) -> bool {
+        for _ in 0..MAX_TURNS {
+            if self.guard_can_move() {
+                self.guard.advance();
+                return true;
+            }
+            self.guard.rotate(&self.policy)
         }
-        self.guard.advance();
+        false
     }
 
     fn guard_can_move(&self) -> bool {
@@ -117,10 +195,7 @@ impl OfficeWithAGuard {
         self.map
             .get(row)
             .and_then(|row| row.get(column))
-            .map(|t| match t {
-                Terrain::Obstacle => true,
-                _ => false,
-            })
+            .map(|t| matches!(t, Terrain::Obstacle))
             .unwrap_or(false)
     }
 
@@ -131,10 +206,13 @@ impl OfficeWithAGuard {
             }
 
             self.register_step();
-            self.guard_step();
+            if !self.guard_step() {
+                return true
+            }
 
+            let state = self.guard_state();
             let was_here = self.path.get(&self.guard.position)
-                .map(|v| v.contains(&self.guard.direction));
+                .map(|v| v.contains(&state));
 
             if was_here.is_some_and(|b| b) {
                 return true
@@ -143,14 +221,22 @@ impl OfficeWithAGuard {
 
     }
 
-    fn clone_with_obstacle(&self, (row, column): Point) -> OfficeWithAGuard {
+    fn clone_with_obstacle(&self, position: Point) -> OfficeWithAGuard {
+        self.clone_with_obstacles(&[position])
+    }
+
+    fn clone_with_obstacles(&self, positions: &[Point]) -> OfficeWithAGuard {
         let mut map = self.map.clone();
-        map[row as usize][column as usize] = Terrain::Obstacle;
+        for (row, column) in positions {
+            map[*row as usize][*column as usize] = Terrain::Obstacle;
+        }
 
         OfficeWithAGuard {
             path: HashMap::new(),
             map,
             guard: self.guard.clone(),
+            guards: self.guards.clone(),
+            policy: self.policy.clone(),
         }
     }
 
@@ -169,6 +255,133 @@ impl OfficeWithAGuard {
 
         res
     }
+
+    // Like `calculate_loop_corrections`, but the guard jumps from turn to turn
+    // with a `JumpTable` and the new obstacle is checked on the fly. Only cells
+    // of the original patrol can change it. Unlike the cell by cell version,
+    // the start is never a candidate since the guard is standing there, so the
+    // two counts differ when an obstacle at the start would close a loop.
+    fn count_loop_obstacles(&self) -> usize {
+        let table = JumpTable::new(&self.map);
+        let start = table.cell(self.guard.position);
+        let mut seen = vec![0u8; table.cells()];
+
+        self.loop_candidates()
+            .into_iter()
+            .filter(|position| {
+                let cell = table.cell(*position);
+                table.loops_with(start, &self.guard.direction, &self.policy, &[cell], &mut seen)
+            })
+            .count()
+    }
+
+    // Cells of the original patrol, sorted, except the start.
+    fn loop_candidates(&self) -> Vec<Point> {
+        let mut candidates: Vec<Point> = self
+            .walk(&self.guard)
+            .into_keys()
+            .filter(|position| *position != self.guard.position)
+            .collect();
+        candidates.sort();
+        candidates
+    }
+}
+
+impl Direction {
+    pub fn from_glyph(glyph: char) -> Option<Direction> {
+        match glyph {
+            '^' => Some(Direction::North),
+            '>' => Some(Direction::East),
+            'v' => Some(Direction::South),
+            '<' => Some(Direction::West),
+            _ => None,
+        }
+    }
+
+    pub fn glyph(&self) -> char {
+        match self {
+            Direction::North => '^',
+            Direction::East => '>',
+            Direction::South => 'v',
+            Direction::West => '<',
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::North => "north",
+            Direction::East => "east",
+            Direction::South => "south",
+            Direction::West => "west",
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        match self {
+            Direction::North => 0,
+            Direction::East => 1,
+            Direction::South => 2,
+            Direction::West => 3,
+        }
+    }
+
+    pub fn delta(&self) -> (isize, isize) {
+        match self {
+            Direction::North => (-1, 0),
+            Direction::East => (0, 1),
+            Direction::South => (1, 0),
+            Direction::West => (0, -1),
+        }
+    }
+
+    pub fn turned_right(&self) -> Direction {
+        match self {
+            Direction::North => Direction::East,
+            Direction::East => Direction::South,
+            Direction::South => Direction::West,
+            Direction::West => Direction::North,
+        }
+    }
+
+    pub fn turned_left(&self) -> Direction {
+        self.turned_right().turned_right().turned_right()
+    }
+
+    pub fn reversed(&self) -> Direction {
+        self.turned_right().turned_right()
+    }
+}
+
+impl PatrolPolicy {
+    pub fn from_name(name: &str) -> Option<PatrolPolicy> {
+        match name {
+            "right" => Some(PatrolPolicy::TurnRight),
+            "left" => Some(PatrolPolicy::TurnLeft),
+            "reverse" => Some(PatrolPolicy::Reverse),
+            "alternating" => Some(PatrolPolicy::Alternating),
+            _ => None,
+        }
+    }
+
+    // Direction after the guard already turned `turns` times.
+    pub fn turn(&self, direction: &Direction, turns: usize) -> Direction {
+        match self {
+            PatrolPolicy::TurnRight => direction.turned_right(),
+            PatrolPolicy::TurnLeft => direction.turned_left(),
+            PatrolPolicy::Reverse => direction.reversed(),
+            PatrolPolicy::Alternating if turns % 2 == 0 => direction.turned_right(),
+            PatrolPolicy::Alternating => direction.turned_left(),
+        }
+    }
+
+    // Part of the guard state besides position and direction: two guards in
+    // the same place can still turn differently later.
+    pub fn phase(&self, turns: usize) -> usize {
+        match self {
+            PatrolPolicy::Alternating => turns % 2,
+            _ => 0,
+        }
+    }
 }
 
 impl Guard {
@@ -176,26 +389,19 @@ impl Guard {
         Guard {
             direction,
             position,
+            turns: 0,
         }
     }
 
     pub fn next_position(&self) -> Point {
         let (row, column) = self.position;
-        match self.direction {
-            Direction::North => (row - 1, column),
-            Direction::East => (row, column + 1),
-            Direction::South => (row + 1, column),
-            Direction::West => (row, column - 1),
-        }
+        let (row_delta, column_delta) = self.direction.delta();
+        (row + row_delta, column + column_delta)
     }
 
-    pub fn rotate(&mut self) {
-        self.direction = match self.direction {
-            Direction::North => Direction::East,
-            Direction::East => Direction::South,
-            Direction::South => Direction::West,
-            Direction::West => Direction::North,
-        }
+    pub fn rotate(&mut self, policy: &PatrolPolicy) {
+        self.direction = policy.turn(&self.direction, self.turns);
+        self.turns += 1;
     }
 
     pub fn advance(&mut self) {
@@ -210,11 +416,101 @@ fn step1(input: &str) -> usize {
 
 fn step2(input: &str) -> usize {
     let office = OfficeWithAGuard::from_str(input);
-    office.calculate_loop_corrections()
+    office.loop_obstacles(workers()).len()
+}
+
+fn workers() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+fn bench(input: &str) {
+    let office = OfficeWithAGuard::from_str(input);
+    println!("map: {}x{}", office.map.len(), office.map[0].len());
+
+    let start = Instant::now();
+    let naive = office.calculate_loop_corrections();
+    println!("cell by cell: {} obstacles in {:?}", naive, start.elapsed());
+
+    let start = Instant::now();
+    let jumping = office.count_loop_obstacles();
+    println!("jump table: {} obstacles in {:?}", jumping, start.elapsed());
+
+    let start = Instant::now();
+    let parallel = office.loop_obstacles(workers()).len();
+    println!(
+        "jump table, {} threads: {} obstacles in {:?}",
+        workers(),
+        parallel,
+        start.elapsed()
+    );
 }
 
 fn main() {
     let input = include_str!("../input.txt");
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.iter().any(|arg| arg == "--bench") {
+        bench(input);
+        return;
+    }
+
+    if args.iter().any(|arg| arg == "--guards") {
+        let office = OfficeWithAGuard::from_str(input);
+        for (guard, coverage) in office.guards.iter().zip(office.guard_coverages()) {
+            println!(
+                "guard at {:?} facing {:?}: {} cells",
+                guard.position, guard.direction, coverage
+            );
+        }
+        println!("combined: {}", office.combined_coverage());
+        return;
+    }
+
+    if let Some(name) = args
+        .iter()
+        .position(|arg| arg == "--policy")
+        .and_then(|index| args.get(index + 1))
+    {
+        let policy = PatrolPolicy::from_name(name).expect("unknown policy");
+        let mut office = OfficeWithAGuard::from_str(input).with_policy(policy);
+        println!("loop obstacles: {}", office.count_loop_obstacles());
+        println!("coverage: {}", office.patrol());
+        return;
+    }
+
+    if let Some(format) = args
+        .iter()
+        .position(|arg| arg == "--export")
+        .and_then(|index| args.get(index + 1))
+    {
+        let office = OfficeWithAGuard::from_str(input);
+        match format.as_str() {
+            "csv" => print!("{}", office.path_csv()),
+            "json" => print!("{}", office.path_json()),
+            "map" => print!("{}", office.path_map()),
+            _ => println!("unknown format {}, use csv, json or map", format),
+        }
+        return;
+    }
+
+    if let Some(limit) = args
+        .iter()
+        .position(|arg| arg == "--confine")
+        .and_then(|index| args.get(index + 1))
+    {
+        let limit = limit.parse().unwrap();
+        let office = OfficeWithAGuard::from_str(input);
+        let obstacles = match args.iter().any(|arg| arg == "--exact") {
+            true => office.confining_obstacles(limit),
+            false => office.confining_obstacles_greedy(limit),
+        };
+        match obstacles {
+            Some(obstacles) => println!("{} obstacles: {:?}", obstacles.len(), obstacles),
+            None => println!("the guard cannot be confined with {} obstacles", limit),
+        }
+        return;
+    }
+
     let res1 = step1(input);
     let res2 = step2(input);
     println!("step1: {}", res1);
@@ -224,6 +520,8 @@ fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::jump_table::Stop;
+    use crate::steps::Step;
     use text_block_macros::text_block_fnl;
 
     #[test]
@@ -259,4 +557,463 @@ mod tests {
 
         assert_eq!(step2(input), 6);
     }
+
+    #[test]
+    fn an_obstacle_cannot_be_placed_on_the_start() {
+        // Going back down, an obstacle at the start would send the guard west,
+        // then north into the same turns again.
+        let input = text_block_fnl!(
+            ".##."
+            "...#"
+            "##.."
+            "..^."
+        );
+        let office = OfficeWithAGuard::from_str(input);
+
+        assert_eq!(office.calculate_loop_corrections(), 1);
+        assert_eq!(office.count_loop_obstacles(), 0);
+        assert_eq!(step2(input), 0);
+    }
+
+    fn example() -> &'static str {
+        text_block_fnl!(
+            "....#....."
+            ".........#"
+            ".........."
+            "..#......."
+            ".......#.."
+            ".........."
+            ".#..^....."
+            "........#."
+            "#........."
+            "......#..."
+        )
+    }
+
+    #[test]
+    fn jump_table_stops_before_obstacles_and_at_the_edge() {
+        let office = OfficeWithAGuard::from_str(example());
+        let table = JumpTable::new(&office.map);
+        let start = table.cell((6, 4));
+
+        assert_eq!(
+            table.stop(start, &Direction::North),
+            Stop {
+                cell: table.cell((1, 4)),
+                exits: false
+            }
+        );
+        assert_eq!(
+            table.stop(start, &Direction::West),
+            Stop {
+                cell: table.cell((6, 2)),
+                exits: false
+            }
+        );
+        assert_eq!(
+            table.stop(start, &Direction::South),
+            Stop {
+                cell: table.cell((9, 4)),
+                exits: true
+            }
+        );
+        assert_eq!(
+            table.stop_with(start, &Direction::North, &[table.cell((3, 4))]),
+            Stop {
+                cell: table.cell((4, 4)),
+                exits: false
+            }
+        );
+        assert_eq!(
+            table.stop_with(start, &Direction::North, &[table.cell((3, 5))]),
+            table.stop(start, &Direction::North)
+        );
+    }
+
+    #[test]
+    fn jump_table_finds_the_same_loops_as_walking() {
+        let office = OfficeWithAGuard::from_str(example());
+
+        assert_eq!(office.count_loop_obstacles(), 6);
+        assert_eq!(office.calculate_loop_corrections(), 6);
+    }
+
+    #[test]
+    fn guard_turning_twice_in_a_corner() {
+        let input = text_block_fnl!(
+            "##....#"
+            ".^#...."
+            "......."
+            "#......"
+            ".....#."
+        );
+        let office = OfficeWithAGuard::from_str(input);
+
+        assert_eq!(office.count_loop_obstacles(), 1);
+        assert_eq!(office.calculate_loop_corrections(), 1);
+    }
+
+    #[test]
+    fn guards_can_start_facing_any_direction() {
+        let input = text_block_fnl!(
+            "..#"
+            ".>."
+            "..."
+        );
+        assert_eq!(step1(input), 2);
+
+        let input = text_block_fnl!(
+            "..."
+            "#<."
+            "..."
+        );
+        assert_eq!(step1(input), 2);
+
+        let input = text_block_fnl!(
+            "..."
+            ".v."
+            ".#."
+        );
+        assert_eq!(step1(input), 2);
+    }
+
+    #[test]
+    fn every_guard_patrols_on_its_own() {
+        let input = text_block_fnl!(
+            "....#"
+            ">...."
+            "....."
+            "..^.."
+        );
+        let office = OfficeWithAGuard::from_str(input);
+
+        assert_eq!(office.guards.len(), 2);
+        assert_eq!(office.guard_coverages(), vec![5, 4]);
+        // Both walk through (1, 2).
+        assert_eq!(office.combined_coverage(), 8);
+        assert_eq!(step1(input), 5);
+    }
+
+    #[test]
+    fn a_guard_walking_in_circles_covers_its_loop() {
+        let input = text_block_fnl!(
+            ".#..."
+            "....#"
+            "#^..."
+            "...#."
+        );
+        let office = OfficeWithAGuard::from_str(input);
+
+        assert_eq!(office.guard_coverages(), vec![6]);
+        assert_eq!(office.combined_coverage(), 6);
+    }
+
+    #[test]
+    fn a_single_guard_covers_what_it_patrols() {
+        let office = OfficeWithAGuard::from_str(example());
+
+        assert_eq!(office.guard_coverages(), vec![41]);
+        assert_eq!(office.combined_coverage(), 41);
+        assert_eq!(step1(example()), 41);
+    }
+
+    #[test]
+    fn turning_right_is_the_default_policy() {
+        let mut office = OfficeWithAGuard::from_str(example());
+
+        assert_eq!(office.policy, PatrolPolicy::TurnRight);
+        assert_eq!(office.patrol(), 41);
+    }
+
+    #[test]
+    fn guard_turning_left_or_back() {
+        let input = text_block_fnl!(
+            "#...."
+            "....."
+            "^...."
+        );
+        let mut office = OfficeWithAGuard::from_str(input).with_policy(PatrolPolicy::TurnLeft);
+        // Leaves through the west side right after the first turn.
+        assert_eq!(office.patrol(), 2);
+
+        let input = text_block_fnl!(
+            "....#"
+            "....."
+            "....^"
+        );
+        let mut office = OfficeWithAGuard::from_str(input).with_policy(PatrolPolicy::Reverse);
+        assert_eq!(office.patrol(), 2);
+        let mut office = OfficeWithAGuard::from_str(input).with_policy(PatrolPolicy::TurnRight);
+        assert_eq!(office.patrol(), 2);
+        let mut office = OfficeWithAGuard::from_str(input).with_policy(PatrolPolicy::TurnLeft);
+        assert_eq!(office.patrol(), 6);
+    }
+
+    #[test]
+    fn alternating_guard_can_walk_in_a_zigzag() {
+        let input = text_block_fnl!(
+            ".#..."
+            "....."
+            "#...."
+            "..#.."
+            "^...."
+        );
+        let mut office = OfficeWithAGuard::from_str(input).with_policy(PatrolPolicy::Alternating);
+        assert_eq!(office.patrol(), 8);
+        let mut office = OfficeWithAGuard::from_str(input);
+        assert_eq!(office.patrol(), 4);
+    }
+
+    #[test]
+    fn a_boxed_in_guard_counts_as_a_loop() {
+        let input = text_block_fnl!(
+            ".#."
+            "#^#"
+            ".#."
+        );
+        for policy in [
+            PatrolPolicy::TurnRight,
+            PatrolPolicy::TurnLeft,
+            PatrolPolicy::Reverse,
+            PatrolPolicy::Alternating,
+        ] {
+            let mut office = OfficeWithAGuard::from_str(input).with_policy(policy);
+            assert!(office.search_loop());
+            assert_eq!(office.path.len(), 1);
+        }
+    }
+
+    #[test]
+    fn jump_table_follows_every_policy() {
+        for policy in [
+            PatrolPolicy::TurnRight,
+            PatrolPolicy::TurnLeft,
+            PatrolPolicy::Reverse,
+            PatrolPolicy::Alternating,
+        ] {
+            let office = OfficeWithAGuard::from_str(example()).with_policy(policy.clone());
+            let start = office.guard.position;
+            let naive = office
+                .walk(&office.guard)
+                .keys()
+                .filter(|position| **position != start)
+                .filter(|position| office.clone_with_obstacle(**position).search_loop())
+                .count();
+
+            assert_eq!(office.count_loop_obstacles(), naive, "{:?}", policy);
+        }
+    }
+
+    fn small_patrol() -> &'static str {
+        text_block_fnl!(
+            ".#..."
+            "....#"
+            "....."
+            ".^..."
+        )
+    }
+
+    #[test]
+    fn steps_follow_the_guard_cell_by_cell() {
+        let office = OfficeWithAGuard::from_str(small_patrol());
+        let step = |row, column, direction, turned| Step {
+            position: (row, column),
+            direction,
+            turned,
+        };
+
+        assert_eq!(
+            office.steps().collect::<Vec<Step>>(),
+            vec![
+                step(3, 1, Direction::North, false),
+                step(2, 1, Direction::North, false),
+                step(1, 1, Direction::East, true),
+                step(1, 2, Direction::East, false),
+                step(1, 3, Direction::South, true),
+                step(2, 3, Direction::South, false),
+                step(3, 3, Direction::South, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn steps_cover_the_patrol() {
+        let mut office = OfficeWithAGuard::from_str(example());
+        let positions: HashSet<Point> = office.steps().map(|step| step.position).collect();
+
+        assert_eq!(positions.len(), office.patrol());
+    }
+
+    #[test]
+    fn steps_end_before_repeating_a_loop() {
+        let input = text_block_fnl!(
+            ".#..."
+            "....#"
+            "#^..."
+            "...#."
+        );
+        let office = OfficeWithAGuard::from_str(input);
+
+        // The guard starts on its own loop and goes around it once.
+        assert_eq!(office.steps().count(), 6);
+    }
+
+    #[test]
+    fn export_path_as_csv_and_json() {
+        let office = OfficeWithAGuard::from_str(small_patrol());
+
+        assert_eq!(
+            office.path_csv(),
+            concat!(
+                "row,column,direction,turned\n",
+                "3,1,north,false\n",
+                "2,1,north,false\n",
+                "1,1,east,true\n",
+                "1,2,east,false\n",
+                "1,3,south,true\n",
+                "2,3,south,false\n",
+                "3,3,south,false\n",
+            )
+        );
+        assert!(office.path_json().starts_with(concat!(
+            "{\"path\":[{\"row\":3,\"column\":1,\"direction\":\"north\",\"turned\":false},"
+        )));
+        assert!(office.path_json().ends_with("\"turns\":[[1,1],[1,3]]}\n"));
+    }
+
+    #[test]
+    fn export_path_as_a_map() {
+        let office = OfficeWithAGuard::from_str(small_patrol());
+
+        assert_eq!(
+            office.path_map(),
+            text_block_fnl!(
+                ".#..."
+                ".+-+#"
+                ".|.|."
+                ".^.|."
+            )
+        );
+    }
+
+    // Every set of obstacles of the given size over the free cells, except the start.
+    fn brute_force_confines(office: &OfficeWithAGuard, size: usize) -> bool {
+        let cells: Vec<Point> = (0..office.map.len() as isize)
+            .flat_map(|row| (0..office.map[0].len() as isize).map(move |column| (row, column)))
+            .filter(|p| *p != office.guard.position && !office.is_obstacle_at(*p))
+            .collect();
+        let mut chosen = vec![];
+        brute_force_subsets(office, &cells, size, &mut chosen)
+    }
+
+    fn brute_force_subsets(
+        office: &OfficeWithAGuard,
+        cells: &[Point],
+        size: usize,
+        chosen: &mut Vec<Point>,
+    ) -> bool {
+        if size == 0 {
+            return office.clone_with_obstacles(chosen).search_loop();
+        }
+        (0..cells.len()).any(|index| {
+            chosen.push(cells[index]);
+            let found = brute_force_subsets(office, &cells[index + 1..], size - 1, chosen);
+            chosen.pop();
+            found
+        })
+    }
+
+    #[test]
+    fn one_obstacle_is_enough_in_the_example() {
+        let office = OfficeWithAGuard::from_str(example());
+
+        assert_eq!(office.confining_obstacles(0), None);
+        let obstacles = office.confining_obstacles(3).unwrap();
+        assert_eq!(obstacles.len(), 1);
+        assert!(office.clone_with_obstacles(&obstacles).search_loop());
+        assert_eq!(office.confining_obstacles_greedy(3).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn a_guard_already_walking_in_circles_needs_no_obstacles() {
+        let input = text_block_fnl!(
+            ".#..."
+            "....#"
+            "#^..."
+            "...#."
+        );
+        let office = OfficeWithAGuard::from_str(input);
+
+        assert_eq!(office.confining_obstacles(2), Some(vec![]));
+        assert_eq!(office.confining_obstacles_greedy(2), Some(vec![]));
+    }
+
+    #[test]
+    fn smallest_set_of_obstacles_in_an_empty_room() {
+        let input = text_block_fnl!(
+            "...."
+            "...."
+            ".^.."
+            "...."
+        );
+        let office = OfficeWithAGuard::from_str(input);
+        let obstacles = office.confining_obstacles(4).unwrap();
+        assert_eq!(obstacles.len(), 4);
+
+        assert!(office.clone_with_obstacles(&obstacles).search_loop());
+        assert!(!brute_force_confines(&office, obstacles.len() - 1));
+        assert_eq!(office.confining_obstacles(obstacles.len() - 1), None);
+
+        let greedy = office.confining_obstacles_greedy(6).unwrap();
+        assert!(office.clone_with_obstacles(&greedy).search_loop());
+        assert!(greedy.len() >= obstacles.len());
+    }
+
+    #[test]
+    fn jump_table_stops_at_the_nearest_of_several_obstacles() {
+        let office = OfficeWithAGuard::from_str(example());
+        let table = JumpTable::new(&office.map);
+        let start = table.cell((6, 4));
+        let extras = [table.cell((2, 4)), table.cell((4, 4)), table.cell((6, 3))];
+
+        assert_eq!(
+            table.stop_with(start, &Direction::North, &extras),
+            Stop {
+                cell: table.cell((5, 4)),
+                exits: false
+            }
+        );
+    }
+
+    #[test]
+    fn parallel_search_finds_the_same_obstacles() {
+        for policy in [
+            PatrolPolicy::TurnRight,
+            PatrolPolicy::TurnLeft,
+            PatrolPolicy::Reverse,
+            PatrolPolicy::Alternating,
+        ] {
+            let office = OfficeWithAGuard::from_str(example()).with_policy(policy);
+            let serial: Vec<Point> = office
+                .loop_candidates()
+                .into_iter()
+                .filter(|position| office.clone_with_obstacle(*position).search_loop())
+                .collect();
+
+            for workers in [1, 2, 3, 8, 100] {
+                assert_eq!(office.loop_obstacles(workers), serial);
+            }
+            assert_eq!(office.count_loop_obstacles(), serial.len());
+        }
+    }
+
+    #[test]
+    fn parallel_search_in_the_example() {
+        let office = OfficeWithAGuard::from_str(example());
+
+        assert_eq!(
+            office.loop_obstacles(4),
+            vec![(6, 3), (7, 6), (7, 7), (8, 1), (8, 3), (9, 7)]
+        );
+    }
 }
@@ -0,0 +1,45 @@
+use crate::jump_table::JumpTable;
+use crate::{OfficeWithAGuard, Point};
+use std::thread;
+
+impl OfficeWithAGuard {
+    // Positions where one extra obstacle makes the guard walk in circles,
+    // sorted. Candidates are split in `workers` chunks checked on their own
+    // threads, each with its own buffer of visited states.
+    pub fn loop_obstacles(&self, workers: usize) -> Vec<Point> {
+        let table = JumpTable::new(&self.map);
+        let start = table.cell(self.guard.position);
+        let candidates = self.loop_candidates();
+        let chunk_size = candidates.len().div_ceil(workers.max(1)).max(1);
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = candidates
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    let table = &table;
+                    scope.spawn(move || {
+                        let mut seen = vec![0u8; table.cells()];
+                        chunk
+                            .iter()
+                            .filter(|position| {
+                                table.loops_with(
+                                    start,
+                                    &self.guard.direction,
+                                    &self.policy,
+                                    &[table.cell(**position)],
+                                    &mut seen,
+                                )
+                            })
+                            .cloned()
+                            .collect::<Vec<Point>>()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+}
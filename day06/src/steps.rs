@@ -0,0 +1,67 @@
+use crate::{Direction, Guard, OfficeWithAGuard, Point, MAX_TURNS};
+use std::collections::HashSet;
+
+// A cell of the patrol and the direction the guard leaves it in. `turned`
+// tells if the guard had to turn in this cell before moving on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub position: Point,
+    pub direction: Direction,
+    pub turned: bool,
+}
+
+// The patrol one cell at a time. Ends when the guard leaves the map, is boxed
+// in, or is about to repeat a step it already did.
+pub struct Steps<'a> {
+    office: &'a OfficeWithAGuard,
+    guard: Guard,
+    seen: HashSet<(Point, Direction, usize)>,
+    done: bool,
+}
+
+impl Iterator for Steps<'_> {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Step> {
+        if self.done || !self.office.is_inside(self.guard.position) {
+            return None;
+        }
+
+        let mut turns = 0;
+        while turns < MAX_TURNS && self.office.is_obstacle_at(self.guard.next_position()) {
+            self.guard.rotate(&self.office.policy);
+            turns += 1;
+        }
+        let step = Step {
+            position: self.guard.position,
+            direction: self.guard.direction.clone(),
+            turned: turns > 0,
+        };
+        if turns == MAX_TURNS {
+            self.done = true;
+            return Some(step);
+        }
+
+        let phase = self.office.policy.phase(self.guard.turns);
+        if !self
+            .seen
+            .insert((step.position, step.direction.clone(), phase))
+        {
+            self.done = true;
+            return None;
+        }
+        self.guard.advance();
+        Some(step)
+    }
+}
+
+impl OfficeWithAGuard {
+    pub fn steps(&self) -> Steps {
+        Steps {
+            office: self,
+            guard: self.guard.clone(),
+            seen: HashSet::new(),
+            done: false,
+        }
+    }
+}
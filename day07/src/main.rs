@@ -1,32 +1,36 @@
+use crate::operation::{parse_operations, Concat, Operation, Prod, Sum};
+use std::rc::Rc;
+use std::time::Instant;
+
+mod operation;
+mod solver;
+
 pub struct EquationLine {
     expected: usize,
     elements: Vec<usize>,
-    allowed_operations: Vec<Operation>,
+    allowed_operations: Vec<Rc<dyn Operation>>,
+}
+
+fn step1_operations() -> Vec<Rc<dyn Operation>> {
+    vec![Rc::new(Sum), Rc::new(Prod)]
 }
 
-#[derive(Debug, Clone)]
-pub enum Operation {
-    Sum,
-    Prod,
-    Concat,
+fn step2_operations() -> Vec<Rc<dyn Operation>> {
+    vec![Rc::new(Sum), Rc::new(Prod), Rc::new(Concat { base: 10 })]
 }
 
+#[cfg(test)]
 fn usize_len(n: usize) -> u32 {
-    let mut n = n;
-    let mut res = 1;
-    while n / 10 > 0 {
-        res += 1;
-        n = n / 10;
-    }
-    res
+    operation::digits(n, 10)
 }
 
+#[cfg(test)]
 fn concat_usize(a: usize, b: usize) -> usize {
-    a * (10usize.pow(usize_len(b))) + b
+    Concat { base: 10 }.apply(a, b).unwrap()
 }
 
 impl EquationLine {
-    pub fn from_str(input: &str, operations: Vec<Operation>) -> EquationLine {
+    pub fn from_str(input: &str, operations: Vec<Rc<dyn Operation>>) -> EquationLine {
         let mut parts = input.split(":");
         let expected = parts.next().unwrap().trim();
         let elements = parts
@@ -46,39 +50,39 @@ impl EquationLine {
     }
 
     pub fn calibration_result(&self) -> usize {
-        if self.can_achieve_equality() {
+        if self.solvable() {
             self.expected
         } else {
             0
         }
     }
 
+    // Tries every combination of operations. Kept to check `solvable` against.
     fn can_achieve_equality(&self) -> bool {
-        let possibilities: Vec<Vec<Operation>> =
+        let possibilities: Vec<Vec<Rc<dyn Operation>>> =
             self.calculate_possibilities(self.elements.len() - 1);
         possibilities.iter().any(|operations| {
-            let calculated = self
-                .elements
+            let calculated = operations
                 .iter()
-                .cloned()
-                .enumerate()
-                .reduce(|(index, a), (next_index, b)| {
-                    let partial = match operations[index] {
-                        Operation::Sum => a + b,
-                        Operation::Prod => a * b,
-                        Operation::Concat => concat_usize(a, b),
-                    };
-
-                    (next_index, partial)
-                })
-                .map(|(_, a)| a)
-                .unwrap();
-            calculated == self.expected
+                .zip(&self.elements[1..])
+                .try_fold(self.elements[0], |partial, (operation, b)| {
+                    operation.apply(partial, *b)
+                });
+            calculated == Some(self.expected)
         })
     }
 
-    fn calculate_possibilities(&self, final_length: usize) -> Vec<Vec<Operation>> {
-        let mut all_possibilities: Vec<Vec<Operation>> = self
+    fn evaluate_random(&self, next: &mut impl FnMut(u64) -> usize) -> usize {
+        self.elements[1..]
+            .iter()
+            .fold(self.elements[0], |acc, element| {
+                let index = next(self.allowed_operations.len() as u64);
+                self.allowed_operations[index].apply(acc, *element).unwrap()
+            })
+    }
+
+    fn calculate_possibilities(&self, final_length: usize) -> Vec<Vec<Rc<dyn Operation>>> {
+        let mut all_possibilities: Vec<Vec<Rc<dyn Operation>>> = self
             .allowed_operations
             .iter()
             .map(|o| vec![o.clone()])
@@ -101,8 +105,8 @@ impl EquationLine {
 fn step1(input: &str) -> usize {
     input
         .lines()
-        .filter(|l| l.len() > 0)
-        .map(|str| EquationLine::from_str(str, vec![Operation::Sum, Operation::Prod]))
+        .filter(|l| !l.is_empty())
+        .map(|str| EquationLine::from_str(str, step1_operations()))
         .map(|e| e.calibration_result())
         .sum()
 }
@@ -110,19 +114,108 @@ fn step1(input: &str) -> usize {
 fn step2(input: &str) -> usize {
     input
         .lines()
-        .filter(|l| l.len() > 0)
-        .map(|str| {
-            EquationLine::from_str(
-                str,
-                vec![Operation::Sum, Operation::Prod, Operation::Concat],
-            )
-        })
+        .filter(|l| !l.is_empty())
+        .map(|str| EquationLine::from_str(str, step2_operations()))
         .map(|e| e.calibration_result())
         .sum()
 }
 
+// Deterministic pseudo random lines, about half of them solvable.
+fn generate_lines(count: usize, operands: usize) -> Vec<EquationLine> {
+    let mut state = 0x2545f4914f6cdd1du64;
+    let mut next = move |max: u64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % max) as usize
+    };
+
+    let operations = step2_operations();
+    (0..count)
+        .map(|index| {
+            let elements: Vec<usize> = (0..operands).map(|_| next(9) + 1).collect();
+            let mut line = EquationLine {
+                expected: 0,
+                elements,
+                allowed_operations: operations.clone(),
+            };
+            line.expected = match index % 2 {
+                0 => line.evaluate_random(&mut next),
+                _ => line.evaluate_random(&mut next) + 1,
+            };
+            line
+        })
+        .collect()
+}
+
+fn bench(operands: usize) {
+    let lines = generate_lines(20, operands);
+    println!("{} lines of {} operands", lines.len(), operands);
+
+    let start = Instant::now();
+    let brute_force = lines.iter().filter(|l| l.can_achieve_equality()).count();
+    println!(
+        "every combination: {} solvable in {:?}",
+        brute_force,
+        start.elapsed()
+    );
+
+    let start = Instant::now();
+    let backward = lines.iter().filter(|l| l.solvable()).count();
+    println!("backward: {} solvable in {:?}", backward, start.elapsed());
+}
+
 fn main() {
     let input = include_str!("../input.txt");
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.iter().any(|arg| arg == "--bench") {
+        bench(12);
+        return;
+    }
+
+    let operations = match args.iter().position(|arg| arg == "--ops") {
+        Some(index) => match args.get(index + 1) {
+            Some(names) => Some(parse_operations(names)),
+            None => Some(Err("--ops needs a list of operations".to_string())),
+        },
+        None => None,
+    };
+    let operations = match operations {
+        Some(Ok(operations)) => Some(operations),
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        None => None,
+    };
+
+    if args.iter().any(|arg| arg == "--explain") {
+        let all = args.iter().any(|arg| arg == "--all");
+        let operations = operations.unwrap_or_else(step2_operations);
+        for line in input.lines().filter(|l| !l.is_empty()) {
+            let line = EquationLine::from_str(line, operations.clone());
+            let witnesses = match all {
+                true => line.witnesses(),
+                false => line.witness().into_iter().collect(),
+            };
+            for witness in witnesses {
+                println!("{}", witness);
+            }
+        }
+        return;
+    }
+
+    if let Some(operations) = operations {
+        let res: usize = input
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(|l| EquationLine::from_str(l, operations.clone()).calibration_result())
+            .sum();
+        println!("calibration: {}", res);
+        return;
+    }
+
     let res1 = step1(input);
     let res2 = step2(input);
     println!("step1: {}", res1);
@@ -203,4 +296,69 @@ mod tests {
         assert_eq!(usize_len(0), 1);
         assert_eq!(usize_len(12345), 5);
     }
+
+    #[test]
+    fn backward_solver_agrees_with_every_combination() {
+        for line in generate_lines(200, 6) {
+            assert_eq!(line.solvable(), line.can_achieve_equality());
+
+            let two_operations = EquationLine {
+                expected: line.expected,
+                elements: line.elements.clone(),
+                allowed_operations: step1_operations(),
+            };
+            assert_eq!(
+                two_operations.solvable(),
+                two_operations.can_achieve_equality()
+            );
+        }
+    }
+
+    #[test]
+    fn backward_solver_with_zeros() {
+        let line = EquationLine::from_str("0: 5 3 0", step1_operations());
+        assert!(line.solvable());
+        assert!(line.can_achieve_equality());
+
+        let line = EquationLine::from_str("7: 0 7", vec![Rc::new(Concat { base: 10 })]);
+        assert!(line.solvable());
+        assert!(line.can_achieve_equality());
+
+        let line = EquationLine::from_str("70: 7 0", vec![Rc::new(Concat { base: 10 })]);
+        assert!(line.solvable());
+        let line = EquationLine::from_str("7: 7 0", vec![Rc::new(Concat { base: 10 })]);
+        assert!(!line.solvable());
+        assert!(!line.can_achieve_equality());
+    }
+
+    #[test]
+    fn backward_solver_agrees_with_every_combination_for_any_operations() {
+        for names in [
+            "sub,xor",
+            "sum,max",
+            "prod,concat2,xor",
+            "sum,prod,concat,sub,xor,max",
+        ] {
+            for line in generate_lines(50, 5) {
+                let line = EquationLine {
+                    expected: line.expected % 1000,
+                    elements: line.elements,
+                    allowed_operations: parse_operations(names).unwrap(),
+                };
+                assert_eq!(line.solvable(), line.can_achieve_equality(), "{}", names);
+                let witnesses = line.witnesses();
+                assert_eq!(!witnesses.is_empty(), line.solvable());
+                for witness in witnesses {
+                    let calculated = witness
+                        .operations
+                        .iter()
+                        .zip(&witness.elements[1..])
+                        .try_fold(witness.elements[0], |partial, (operation, b)| {
+                            operation.apply(partial, *b)
+                        });
+                    assert_eq!(calculated, Some(line.expected), "{}", witness);
+                }
+            }
+        }
+    }
 }
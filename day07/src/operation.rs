@@ -0,0 +1,239 @@
+use std::fmt;
+use std::rc::Rc;
+
+// What the left operand must be for `left <operation> right` to give a target.
+#[derive(Debug, PartialEq)]
+pub enum Inversion {
+    // The operation cannot be undone, the solver has to try every left value.
+    Unsupported,
+    Impossible,
+    Left(usize),
+    // Whatever the left side is, like when multiplying by zero.
+    AnyLeft,
+}
+
+pub trait Operation: fmt::Debug {
+    fn symbol(&self) -> String;
+
+    // `None` when the result does not fit in a `usize` or is not defined.
+    fn apply(&self, left: usize, right: usize) -> Option<usize>;
+
+    fn invert(&self, _target: usize, _right: usize) -> Inversion {
+        Inversion::Unsupported
+    }
+}
+
+#[derive(Debug)]
+pub struct Sum;
+
+#[derive(Debug)]
+pub struct Prod;
+
+// Digits of the right operand written after the ones of the left operand.
+#[derive(Debug)]
+pub struct Concat {
+    pub base: usize,
+}
+
+#[derive(Debug)]
+pub struct Sub;
+
+#[derive(Debug)]
+pub struct Xor;
+
+#[derive(Debug)]
+pub struct Max;
+
+pub fn digits(n: usize, base: usize) -> u32 {
+    let mut n = n;
+    let mut res = 1;
+    while n / base > 0 {
+        res += 1;
+        n /= base;
+    }
+    res
+}
+
+impl Operation for Sum {
+    fn symbol(&self) -> String {
+        "+".to_string()
+    }
+
+    fn apply(&self, left: usize, right: usize) -> Option<usize> {
+        left.checked_add(right)
+    }
+
+    fn invert(&self, target: usize, right: usize) -> Inversion {
+        match target.checked_sub(right) {
+            Some(left) => Inversion::Left(left),
+            None => Inversion::Impossible,
+        }
+    }
+}
+
+impl Operation for Prod {
+    fn symbol(&self) -> String {
+        "*".to_string()
+    }
+
+    fn apply(&self, left: usize, right: usize) -> Option<usize> {
+        left.checked_mul(right)
+    }
+
+    fn invert(&self, target: usize, right: usize) -> Inversion {
+        match (right, target) {
+            (0, 0) => Inversion::AnyLeft,
+            (0, _) => Inversion::Impossible,
+            _ if target % right == 0 => Inversion::Left(target / right),
+            _ => Inversion::Impossible,
+        }
+    }
+}
+
+impl Concat {
+    fn shift(&self, right: usize) -> Option<usize> {
+        self.base.checked_pow(digits(right, self.base))
+    }
+}
+
+impl Operation for Concat {
+    fn symbol(&self) -> String {
+        match self.base {
+            10 => "||".to_string(),
+            base => format!("||{}", base),
+        }
+    }
+
+    fn apply(&self, left: usize, right: usize) -> Option<usize> {
+        left.checked_mul(self.shift(right)?)?.checked_add(right)
+    }
+
+    fn invert(&self, target: usize, right: usize) -> Inversion {
+        match self.shift(right) {
+            Some(shift) if target % shift == right => Inversion::Left(target / shift),
+            _ => Inversion::Impossible,
+        }
+    }
+}
+
+impl Operation for Sub {
+    fn symbol(&self) -> String {
+        "-".to_string()
+    }
+
+    fn apply(&self, left: usize, right: usize) -> Option<usize> {
+        left.checked_sub(right)
+    }
+
+    fn invert(&self, target: usize, right: usize) -> Inversion {
+        match target.checked_add(right) {
+            Some(left) => Inversion::Left(left),
+            None => Inversion::Impossible,
+        }
+    }
+}
+
+impl Operation for Xor {
+    fn symbol(&self) -> String {
+        "^".to_string()
+    }
+
+    fn apply(&self, left: usize, right: usize) -> Option<usize> {
+        Some(left ^ right)
+    }
+
+    fn invert(&self, target: usize, right: usize) -> Inversion {
+        Inversion::Left(target ^ right)
+    }
+}
+
+// Not invertible: when the right operand is the target, any smaller left
+// operand works too.
+impl Operation for Max {
+    fn symbol(&self) -> String {
+        "max".to_string()
+    }
+
+    fn apply(&self, left: usize, right: usize) -> Option<usize> {
+        Some(left.max(right))
+    }
+}
+
+// Operations by name, separated by commas: `sum`, `prod`, `concat`, `sub`,
+// `xor`, `max`, and `concat` followed by a base like `concat2`.
+pub fn parse_operations(names: &str) -> Result<Vec<Rc<dyn Operation>>, String> {
+    names
+        .split(',')
+        .map(|name| -> Result<Rc<dyn Operation>, String> {
+            match name.trim() {
+                "sum" => Ok(Rc::new(Sum)),
+                "prod" => Ok(Rc::new(Prod)),
+                "sub" => Ok(Rc::new(Sub)),
+                "xor" => Ok(Rc::new(Xor)),
+                "max" => Ok(Rc::new(Max)),
+                "concat" => Ok(Rc::new(Concat { base: 10 })),
+                name => match name.strip_prefix("concat").map(|base| base.parse()) {
+                    Some(Ok(base)) if base >= 2 => Ok(Rc::new(Concat { base })),
+                    _ => Err(format!("unknown operation {}", name)),
+                },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invert_every_operation() {
+        assert_eq!(Sum.invert(190, 19), Inversion::Left(171));
+        assert_eq!(Sum.invert(10, 19), Inversion::Impossible);
+        assert_eq!(Prod.invert(190, 19), Inversion::Left(10));
+        assert_eq!(Prod.invert(191, 19), Inversion::Impossible);
+        assert_eq!(Prod.invert(0, 0), Inversion::AnyLeft);
+        assert_eq!(Prod.invert(5, 0), Inversion::Impossible);
+        assert_eq!(Sub.invert(3, 4), Inversion::Left(7));
+        assert_eq!(Xor.invert(6, 3), Inversion::Left(5));
+        assert_eq!(Max.invert(6, 3), Inversion::Unsupported);
+    }
+
+    #[test]
+    fn invert_concatenation() {
+        let decimal = Concat { base: 10 };
+        assert_eq!(decimal.invert(156, 6), Inversion::Left(15));
+        assert_eq!(decimal.invert(156, 56), Inversion::Left(1));
+        assert_eq!(decimal.invert(156, 156), Inversion::Left(0));
+        assert_eq!(decimal.invert(156, 5), Inversion::Impossible);
+        assert_eq!(decimal.invert(5, 0), Inversion::Impossible);
+        assert_eq!(decimal.invert(50, 0), Inversion::Left(5));
+
+        // 0b101 || 0b11 = 0b10111
+        let binary = Concat { base: 2 };
+        assert_eq!(binary.apply(5, 3), Some(23));
+        assert_eq!(binary.invert(23, 3), Inversion::Left(5));
+        assert_eq!(binary.invert(22, 3), Inversion::Impossible);
+    }
+
+    #[test]
+    fn operations_report_overflow_and_undefined_results() {
+        assert_eq!(Sum.apply(usize::MAX, 1), None);
+        assert_eq!(Prod.apply(usize::MAX, 2), None);
+        assert_eq!(Concat { base: 10 }.apply(usize::MAX, 1), None);
+        assert_eq!(Sub.apply(3, 4), None);
+        assert_eq!(Max.apply(3, 4), Some(4));
+    }
+
+    #[test]
+    fn parse_operations_by_name() {
+        let symbols: Vec<String> = parse_operations("sum,prod,concat,concat2,sub,xor,max")
+            .unwrap()
+            .iter()
+            .map(|o| o.symbol())
+            .collect();
+
+        assert_eq!(symbols, vec!["+", "*", "||", "||2", "-", "^", "max"]);
+        assert!(parse_operations("sum,div").is_err());
+        assert!(parse_operations("concat1").is_err());
+    }
+}
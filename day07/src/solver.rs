@@ -0,0 +1,296 @@
+use crate::operation::{Inversion, Operation};
+use crate::EquationLine;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::rc::Rc;
+
+// A line together with operations that make it true.
+#[derive(Debug, Clone)]
+pub struct Witness {
+    pub expected: usize,
+    pub elements: Vec<usize>,
+    pub operations: Vec<Rc<dyn Operation>>,
+}
+
+impl fmt::Display for Witness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.elements[0])?;
+        for (operation, element) in self.operations.iter().zip(&self.elements[1..]) {
+            write!(f, " {} {}", operation.symbol(), element)?;
+        }
+        write!(f, " = {}", self.expected)
+    }
+}
+
+impl EquationLine {
+    // Works from `expected` back to the first element, undoing the last
+    // operation at every step. Most operations cannot be undone (the target is
+    // smaller than the element, is not a multiple of it, or does not end with
+    // its digits), so whole subtrees of combinations are never explored.
+    // Operations without an inverse are checked against every value the
+    // elements before them can give, computed going forwards.
+    pub fn solvable(&self) -> bool {
+        self.reaches(self.expected, self.elements.len())
+    }
+
+    // Operations, from left to right, that make the line true.
+    pub fn witness(&self) -> Option<Witness> {
+        let mut found = vec![];
+        self.collect(
+            self.expected,
+            self.elements.len(),
+            &mut vec![],
+            &mut found,
+            false,
+        );
+        found
+            .pop()
+            .map(|operations| self.with_operations(operations))
+    }
+
+    // Every way of making the line true.
+    pub fn witnesses(&self) -> Vec<Witness> {
+        let mut found = vec![];
+        self.collect(
+            self.expected,
+            self.elements.len(),
+            &mut vec![],
+            &mut found,
+            true,
+        );
+        found
+            .into_iter()
+            .map(|operations| self.with_operations(operations))
+            .collect()
+    }
+
+    fn with_operations(&self, operations: Vec<Rc<dyn Operation>>) -> Witness {
+        Witness {
+            expected: self.expected,
+            elements: self.elements.clone(),
+            operations,
+        }
+    }
+
+    // Same search as `reaches`, keeping the operations undone so far in
+    // `undone`, last one first. Stops at the first solution unless `all`.
+    fn collect(
+        &self,
+        target: usize,
+        count: usize,
+        undone: &mut Vec<Rc<dyn Operation>>,
+        found: &mut Vec<Vec<Rc<dyn Operation>>>,
+        all: bool,
+    ) {
+        if count == 1 {
+            if self.elements[0] == target {
+                found.push(undone.iter().rev().cloned().collect());
+            }
+            return;
+        }
+
+        let right = self.elements[count - 1];
+        for operation in &self.allowed_operations {
+            if !all && !found.is_empty() {
+                return;
+            }
+            undone.push(operation.clone());
+            match operation.invert(target, right) {
+                Inversion::Impossible => {}
+                Inversion::Left(left) => self.collect(left, count - 1, undone, found, all),
+                Inversion::Unsupported => {
+                    for left in self.lefts(operation.as_ref(), target, count) {
+                        self.collect(left, count - 1, undone, found, all);
+                    }
+                }
+                Inversion::AnyLeft => {
+                    // Any operations between the first `count - 1` elements
+                    // work, as long as they give a value at all.
+                    let mut prefixes = vec![];
+                    self.defined(
+                        self.elements[0],
+                        1,
+                        count - 1,
+                        &mut vec![],
+                        &mut prefixes,
+                        all,
+                    );
+                    for prefix in prefixes {
+                        found.push(
+                            prefix
+                                .into_iter()
+                                .chain(undone.iter().rev().cloned())
+                                .collect(),
+                        );
+                    }
+                }
+            }
+            undone.pop();
+        }
+    }
+
+    // Operations between the first `count` elements that never give an
+    // undefined result, going forwards from `value` at `index`. Stops at the
+    // first one unless `all`.
+    fn defined(
+        &self,
+        value: usize,
+        index: usize,
+        count: usize,
+        operations: &mut Vec<Rc<dyn Operation>>,
+        found: &mut Vec<Vec<Rc<dyn Operation>>>,
+        all: bool,
+    ) {
+        if index == count {
+            found.push(operations.clone());
+            return;
+        }
+        for operation in &self.allowed_operations {
+            if !all && !found.is_empty() {
+                return;
+            }
+            if let Some(next) = operation.apply(value, self.elements[index]) {
+                operations.push(operation.clone());
+                self.defined(next, index + 1, count, operations, found, all);
+                operations.pop();
+            }
+        }
+    }
+
+    // Whether the first `count` elements can give `target`.
+    fn reaches(&self, target: usize, count: usize) -> bool {
+        if count == 1 {
+            return self.elements[0] == target;
+        }
+        let right = self.elements[count - 1];
+        self.allowed_operations
+            .iter()
+            .any(|operation| match operation.invert(target, right) {
+                Inversion::Impossible => false,
+                Inversion::Left(left) => self.reaches(left, count - 1),
+                Inversion::Unsupported => !self.lefts(operation.as_ref(), target, count).is_empty(),
+                Inversion::AnyLeft => !self.values(count - 1).is_empty(),
+            })
+    }
+
+    // Values of the first `count - 1` elements that `operation` turns into
+    // `target` with the next element.
+    fn lefts(&self, operation: &dyn Operation, target: usize, count: usize) -> Vec<usize> {
+        let right = self.elements[count - 1];
+        self.values(count - 1)
+            .into_iter()
+            .filter(|left| operation.apply(*left, right) == Some(target))
+            .collect()
+    }
+
+    // Every value the first `count` elements can give.
+    fn values(&self, count: usize) -> BTreeSet<usize> {
+        let mut values = BTreeSet::from([self.elements[0]]);
+        for right in &self.elements[1..count] {
+            values = values
+                .iter()
+                .flat_map(|left| {
+                    self.allowed_operations
+                        .iter()
+                        .filter_map(|operation| operation.apply(*left, *right))
+                })
+                .collect();
+        }
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::parse_operations;
+    use crate::step2_operations;
+
+    fn line(input: &str) -> EquationLine {
+        EquationLine::from_str(input, step2_operations())
+    }
+
+    fn symbols(witness: &Witness) -> Vec<String> {
+        witness.operations.iter().map(|o| o.symbol()).collect()
+    }
+
+    #[test]
+    fn witness_of_a_solvable_line() {
+        let witness = line("3267: 81 40 27").witness().unwrap();
+
+        assert_eq!(symbols(&witness), vec!["*", "+"]);
+        assert_eq!(witness.to_string(), "81 * 40 + 27 = 3267");
+        assert_eq!(
+            line("7290: 6 8 6 15").witness().unwrap().to_string(),
+            "6 * 8 || 6 * 15 = 7290"
+        );
+        assert!(line("83: 17 5").witness().is_none());
+    }
+
+    #[test]
+    fn every_witness_of_a_line() {
+        let witnesses: Vec<String> = line("3267: 81 40 27")
+            .witnesses()
+            .iter()
+            .map(|w| w.to_string())
+            .collect();
+
+        assert_eq!(
+            witnesses,
+            vec!["81 * 40 + 27 = 3267", "81 + 40 * 27 = 3267"]
+        );
+        assert!(line("83: 17 5").witnesses().is_empty());
+    }
+
+    #[test]
+    fn multiplying_by_zero_makes_any_prefix_a_witness() {
+        let witnesses = line("0: 5 3 0").witnesses();
+
+        assert_eq!(witnesses.len(), 3);
+        assert!(witnesses.iter().all(|w| symbols(w)[1] == "*"));
+        assert_eq!(
+            line("0: 5 3 0").witness().unwrap().to_string(),
+            "5 + 3 * 0 = 0"
+        );
+    }
+
+    #[test]
+    fn multiplying_by_zero_needs_a_defined_prefix() {
+        let line = EquationLine::from_str("0: 1 5 0", parse_operations("sub,prod").unwrap());
+        let witnesses: Vec<String> = line.witnesses().iter().map(|w| w.to_string()).collect();
+        assert_eq!(witnesses, vec!["1 * 5 * 0 = 0"]);
+
+        let line = EquationLine::from_str(
+            "0: 2 10000000000000000000 0",
+            parse_operations("sub,prod").unwrap(),
+        );
+        assert!(!line.solvable());
+        assert!(!line.can_achieve_equality());
+        assert!(line.witness().is_none());
+    }
+
+    #[test]
+    fn operations_without_inverse_are_searched_forwards() {
+        let operations = parse_operations("sum,max").unwrap();
+        let line = EquationLine::from_str("12: 3 9 2 1", operations.clone());
+        assert!(line.solvable());
+        assert_eq!(line.witness().unwrap().to_string(), "3 max 9 + 2 + 1 = 12");
+
+        let line = EquationLine::from_str("9: 3 9 2 1", operations.clone());
+        assert_eq!(line.witnesses().len(), 1);
+        let line = EquationLine::from_str("16: 3 9 2 1", operations);
+        assert!(!line.solvable());
+    }
+
+    #[test]
+    fn subtraction_xor_and_other_bases() {
+        let line = EquationLine::from_str("6: 10 3 1", parse_operations("sub,xor").unwrap());
+        assert_eq!(line.witness().unwrap().to_string(), "10 - 3 - 1 = 6");
+        let line = EquationLine::from_str("8: 10 3 1", parse_operations("sub,xor").unwrap());
+        assert_eq!(line.witness().unwrap().to_string(), "10 ^ 3 - 1 = 8");
+
+        // 0b101 || 0b11 = 0b10111
+        let line = EquationLine::from_str("23: 5 3", parse_operations("concat2").unwrap());
+        assert_eq!(line.witness().unwrap().to_string(), "5 ||2 3 = 23");
+    }
+}